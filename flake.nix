@@ -39,6 +39,12 @@
               description = "The sundial package to use";
             };
 
+            daemon = mkOption {
+              type = types.bool;
+              default = false;
+              description = "Keep sundial running in the background instead of triggering it with a timer";
+            };
+
             interval = mkOption {
               type = types.str;
               default = "*:0/5";
@@ -55,7 +61,12 @@
           config = mkIf config.services.sundial.enable {
             systemd.user.services.sundial = {
               Unit.Description = "sets screen temperature based on sunrise/sunset times";
-              Service = {
+              Service = if config.services.sundial.daemon then {
+                Type = "simple";
+                ExecStart = "${config.services.sundial.package}/bin/sundial daemon";
+                Environment = "RUST_LOG=${config.services.sundial.logLevel}";
+                Restart = "on-failure";
+              } else {
                 Type = "oneshot";
                 ExecStart = "${config.services.sundial.package}/bin/sundial";
                 Environment = "RUST_LOG=${config.services.sundial.logLevel}";
@@ -63,7 +74,7 @@
               Install.WantedBy = [ "hyprland-session.target" ];
             };
 
-            systemd.user.timers.sundial = {
+            systemd.user.timers.sundial = mkIf (!config.services.sundial.daemon) {
               Unit.Description = "timer for sundial service";
               Timer = {
                 Unit = "sundial.service";
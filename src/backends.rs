@@ -0,0 +1,528 @@
+use std::io::Write;
+use std::process::Command;
+use std::time::Duration;
+use log::{debug, info};
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::ConnectionExt as _;
+
+use crate::config::{BackendKind, Config};
+use crate::hyprctl::{Hyprctl, HyprctlError};
+use crate::ipc::{HyprsunsetIpc, IpcError};
+use crate::retry::with_retries;
+use crate::screen::ScreenState;
+
+// D-Bus coordinates of wl-gammarelay-rs
+const WL_GAMMARELAY_SERVICE: &str = "rs.wl-gammarelay";
+const WL_GAMMARELAY_INTERFACE: &str = "rs.wl.gammarelay";
+// Brightness range gammastep and redshift accept, used for every backend
+// that takes a brightness factor so the same config looks the same on each
+const BRIGHTNESS_RANGE: std::ops::RangeInclusive<f64> = 0.1..=1.0;
+
+pub trait OutputBackend {
+    fn name(&self) -> &'static str;
+
+    // Starts whatever applies screen states, when it needs to be running
+    fn prepare(&self, _dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    // With dry_run, writes what it would do there instead
+    fn apply(&self, screen_state: &ScreenState, dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+pub struct HyprctlBackend {
+    pub hyprctl: Hyprctl,
+    pub startup_timeout: Duration,
+}
+
+impl OutputBackend for HyprctlBackend {
+    fn name(&self) -> &'static str {
+        "hyprctl"
+    }
+
+    fn prepare(&self, dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+        start_hyprsunset(dry_run, self.startup_timeout)
+    }
+
+    fn apply(&self, screen_state: &ScreenState, dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(output) = dry_run {
+            for command in hyprsunset_commands(screen_state) {
+                writeln!(output, "[dry-run] Would run: hyprctl hyprsunset {}", command)?;
+            }
+
+            return Ok(());
+        }
+
+        for command in hyprsunset_commands(screen_state) {
+            with_retries(&format!("hyprctl hyprsunset {}", command), HyprctlError::is_transient, || self.hyprctl.hyprsunset(&command))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct HyprsunsetIpcBackend {
+    // Fails outside of a Hyprland session, which only matters when not
+    // dry-running
+    pub ipc: Result<HyprsunsetIpc, IpcError>,
+    pub startup_timeout: Duration,
+}
+
+impl OutputBackend for HyprsunsetIpcBackend {
+    fn name(&self) -> &'static str {
+        "hyprsunset_ipc"
+    }
+
+    fn prepare(&self, dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+        start_hyprsunset(dry_run, self.startup_timeout)
+    }
+
+    fn apply(&self, screen_state: &ScreenState, dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(output) = dry_run {
+            let socket = match &self.ipc {
+                Ok(ipc) => ipc.socket_path.display().to_string(),
+                Err(_) => "hyprsunset".to_string(),
+            };
+            for command in hyprsunset_commands(screen_state) {
+                writeln!(output, "[dry-run] Would send to {}: {}", socket, command)?;
+            }
+
+            return Ok(());
+        }
+
+        let ipc = self.ipc.as_ref().map_err(|error| error.to_string())?;
+        for command in hyprsunset_commands(screen_state) {
+            with_retries(&format!("Sending \"{}\" to hyprsunset", command), IpcError::is_transient, || ipc.send(&command))?;
+        }
+
+        Ok(())
+    }
+}
+
+// wl-gammarelay-rs, driven over D-Bus with busctl
+pub struct WlGammarelayBackend;
+
+impl OutputBackend for WlGammarelayBackend {
+    fn name(&self) -> &'static str {
+        "wl_gammarelay"
+    }
+
+    fn apply(&self, screen_state: &ScreenState, dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+        let temperature = screen_state.temperature.to_string();
+        let brightness = brightness(screen_state).to_string();
+        let properties = [("Temperature", "q", temperature.as_str()), ("Brightness", "d", brightness.as_str())];
+        let commands = properties.map(|(property, signature, value)| {
+            vec!["--user", "set-property", WL_GAMMARELAY_SERVICE, "/", WL_GAMMARELAY_INTERFACE, property, signature, value]
+        });
+
+        run_commands("busctl", &commands, dry_run)
+    }
+}
+
+// One-shot mode of gammastep and redshift, which share their flags. X11
+// only: on Wayland they go through wlr-gamma-control, where the compositor
+// drops the tint as soon as they exit, so RandR is asked for explicitly
+pub struct OneShotBackend {
+    pub program: &'static str,
+}
+
+impl OutputBackend for OneShotBackend {
+    fn name(&self) -> &'static str {
+        self.program
+    }
+
+    fn apply(&self, screen_state: &ScreenState, dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+        let temperature = screen_state.temperature.to_string();
+        let brightness = brightness(screen_state).to_string();
+
+        // -P resets whatever the previous run left behind
+        run_commands(self.program, &[vec!["-m", "randr", "-P", "-O", &temperature, "-b", &brightness]], dry_run)
+    }
+}
+
+// X11 only: scales the gamma ramps of every CRTC by the temperature's white
+// point and the brightness, the way redshift and sct do. No xrandr needed,
+// it talks RandR to the X server directly
+pub struct XrandrBackend;
+
+impl OutputBackend for XrandrBackend {
+    fn name(&self) -> &'static str {
+        "xrandr"
+    }
+
+    fn apply(&self, screen_state: &ScreenState, dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+        let (red, green, blue) = whitepoint(screen_state.temperature);
+        let brightness = brightness(screen_state);
+
+        if let Some(output) = dry_run {
+            writeln!(
+                output,
+                "[dry-run] Would scale the gamma ramps of every CRTC by {:.3}:{:.3}:{:.3} at brightness {:.2}",
+                red, green, blue, brightness,
+            )?;
+            return Ok(());
+        }
+
+        let (connection, screen_number) = x11rb::connect(None)
+            .map_err(|error| format!("Could not connect to the X server: {}", error))?;
+        let root = connection.setup().roots[screen_number].root;
+        let resources = connection.randr_get_screen_resources_current(root)?.reply()?;
+
+        for crtc in resources.crtcs {
+            let size = connection.randr_get_crtc_gamma_size(crtc)?.reply()?.size;
+            let ramps = [red, green, blue].map(|multiplier| gamma_ramp(size, multiplier * brightness));
+            connection.randr_set_crtc_gamma(crtc, &ramps[0], &ramps[1], &ramps[2])?.check()?;
+        }
+
+        Ok(())
+    }
+}
+
+// A gamma ramp of the given size from black up to multiplier times full
+// intensity, so white itself takes on the tint
+fn gamma_ramp(size: u16, multiplier: f64) -> Vec<u16> {
+    let last = size.saturating_sub(1).max(1) as f64;
+
+    (0..size)
+        .map(|index| (index as f64 / last * multiplier * u16::MAX as f64).clamp(0.0, u16::MAX as f64).round() as u16)
+        .collect()
+}
+
+fn hyprsunset_commands(screen_state: &ScreenState) -> [String; 2] {
+    [
+        format!("temperature {}", screen_state.temperature),
+        format!("gamma {}", screen_state.gamma),
+    ]
+}
+
+// Starts hyprsunset unless it's running, and waits until it takes commands
+fn start_hyprsunset(dry_run: Option<&mut dyn Write>, timeout: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(output) = dry_run {
+        writeln!(output, "[dry-run] Would start hyprsunset unless running: systemctl --user start hyprsunset")?;
+        return Ok(());
+    }
+
+    let hyprsunset_process = Command::new("pgrep")
+        .arg("hyprsunset")
+        .output()?;
+    let is_hyprsunset_running = hyprsunset_process.status.success();
+    if is_hyprsunset_running {
+        debug!("Hyprsunset is running. Process: {:?}", hyprsunset_process);
+        return Ok(());
+    }
+
+    let new_hyprsunset_process = Command::new("systemctl")
+        .args(["--user", "start", "hyprsunset"])
+        .output()?;
+    debug!("Starting hyprsunset. Process: {:?}", new_hyprsunset_process);
+    if !new_hyprsunset_process.status.success() {
+        return Err(format!(
+            "systemctl --user start hyprsunset failed ({}): {}",
+            new_hyprsunset_process.status, String::from_utf8_lossy(&new_hyprsunset_process.stderr).trim(),
+        ).into());
+    }
+
+    HyprsunsetIpc::from_env()?.wait_until_ready(timeout)?;
+    debug!("Hyprsunset is ready");
+
+    Ok(())
+}
+
+// Runs the program once per set of arguments, or with dry_run only writes
+// the commands there
+fn run_commands(program: &str, commands: &[Vec<&str>], dry_run: Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(output) = dry_run {
+        for args in commands {
+            writeln!(output, "[dry-run] Would run: {} {}", program, args.join(" "))?;
+        }
+
+        return Ok(());
+    }
+
+    for args in commands {
+        run_command(program, args)?;
+    }
+
+    Ok(())
+}
+
+fn run_command(program: &str, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|error| format!("Could not run {}: {}", program, error))?;
+    if !output.status.success() {
+        return Err(format!("{} {} failed ({}): {}", program, args.join(" "), output.status, String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+
+    Ok(())
+}
+
+// Red, green and blue multipliers (0 to 1) tinting white to the given color
+// temperature, after Tanner Helland's blackbody approximation
+fn whitepoint(temperature: u32) -> (f64, f64, f64) {
+    let temperature = temperature as f64 / 100.0;
+
+    let red = if temperature <= 66.0 {
+        255.0
+    } else {
+        329.698727446 * (temperature - 60.0).powf(-0.1332047592)
+    };
+    let green = if temperature <= 66.0 {
+        99.4708025861 * temperature.ln() - 161.1195681661
+    } else {
+        288.1221695283 * (temperature - 60.0).powf(-0.0755148492)
+    };
+    let blue = if temperature >= 66.0 {
+        255.0
+    } else if temperature <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * (temperature - 10.0).ln() - 305.0447927307
+    };
+
+    let channel = |value: f64| value.clamp(0.0, 255.0) / 255.0;
+    (channel(red), channel(green), channel(blue))
+}
+
+// The gamma percentage as a brightness factor
+fn brightness(screen_state: &ScreenState) -> f64 {
+    (screen_state.gamma / 100.0).clamp(*BRIGHTNESS_RANGE.start(), *BRIGHTNESS_RANGE.end())
+}
+
+// What auto-detection gets to look at
+pub struct Session {
+    pub hyprland: bool,
+    pub hyprsunset_socket: bool,
+    pub wayland: bool,
+    pub x11: bool,
+    pub wl_gammarelay: bool,
+}
+
+impl Session {
+    // Dry runs don't spawn processes, so they skip asking D-Bus for
+    // wl-gammarelay-rs
+    pub fn current(dry_run: bool) -> Self {
+        let has_variable = |variable: &str| std::env::var_os(variable).is_some_and(|value| !value.is_empty());
+        let hyprsunset_socket = HyprsunsetIpc::from_env().is_ok_and(|ipc| ipc.socket_path.exists());
+        let wl_gammarelay = !dry_run
+            && Command::new("busctl")
+                .args(["--user", "status", WL_GAMMARELAY_SERVICE])
+                .output()
+                .is_ok_and(|output| output.status.success());
+
+        Session {
+            hyprland: has_variable("HYPRLAND_INSTANCE_SIGNATURE"),
+            hyprsunset_socket,
+            wayland: has_variable("WAYLAND_DISPLAY"),
+            x11: has_variable("DISPLAY"),
+            wl_gammarelay,
+        }
+    }
+}
+
+// Hyprland first, then whatever the Wayland compositor or X server can use.
+// Wayland compositors other than Hyprland need wl-gammarelay-rs running
+pub fn detect_backend(session: &Session) -> Option<BackendKind> {
+    if session.hyprland {
+        return Some(if session.hyprsunset_socket { BackendKind::HyprsunsetIpc } else { BackendKind::Hyprctl });
+    }
+
+    if session.wayland {
+        return session.wl_gammarelay.then_some(BackendKind::WlGammarelay);
+    }
+
+    // RandR comes with every X server that matters
+    if session.x11 {
+        return Some(BackendKind::Xrandr);
+    }
+
+    None
+}
+
+// The configured backend, or the detected one. Dry runs fall back to
+// printing hyprctl commands, so they work without any graphical session.
+// The session is only looked at for auto-detection
+fn backend_kind(config: &Config, session: impl FnOnce() -> Session, dry_run: bool) -> Result<BackendKind, &'static str> {
+    if config.output.backend != BackendKind::Auto {
+        return Ok(config.output.backend);
+    }
+
+    match detect_backend(&session()) {
+        Some(kind) => Ok(kind),
+        None if dry_run => {
+            info!("Could not detect an output backend for this session, printing hyprctl commands");
+            Ok(BackendKind::Hyprctl)
+        },
+        None => Err("Could not detect an output backend for this session, set output.backend in the config"),
+    }
+}
+
+pub fn build_backend(config: &Config, dry_run: bool) -> Result<Box<dyn OutputBackend>, Box<dyn std::error::Error>> {
+    let kind = backend_kind(config, || Session::current(dry_run), dry_run)?;
+
+    let startup_timeout = Duration::from_secs(config.output.startup_timeout_in_seconds);
+    let backend: Box<dyn OutputBackend> = match kind {
+        BackendKind::Auto => unreachable!(),
+        BackendKind::Hyprctl => Box::new(HyprctlBackend { hyprctl: Hyprctl::default(), startup_timeout }),
+        BackendKind::HyprsunsetIpc => Box::new(HyprsunsetIpcBackend { ipc: HyprsunsetIpc::from_env(), startup_timeout }),
+        BackendKind::WlGammarelay => Box::new(WlGammarelayBackend),
+        BackendKind::Gammastep => Box::new(OneShotBackend { program: "gammastep" }),
+        BackendKind::Redshift => Box::new(OneShotBackend { program: "redshift" }),
+        BackendKind::Xrandr => Box::new(XrandrBackend),
+    };
+    info!("Output backend: {}", backend.name());
+
+    Ok(backend)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_test_config;
+    use crate::ipc::hyprsunset_socket_path;
+
+    // The lines a dry run writes
+    fn dry_run(run: impl FnOnce(Option<&mut dyn Write>) -> Result<(), Box<dyn std::error::Error>>) -> Vec<String> {
+        let mut output = Vec::new();
+        run(Some(&mut output)).unwrap();
+
+        String::from_utf8(output).unwrap().lines().map(str::to_string).collect()
+    }
+
+    fn session() -> Session {
+        Session {
+            hyprland: false,
+            hyprsunset_socket: false,
+            wayland: false,
+            x11: false,
+            wl_gammarelay: false,
+        }
+    }
+
+    #[test]
+    fn test_detect_backend() {
+        let test_cases = vec![
+            (Session { hyprland: true, wayland: true, hyprsunset_socket: true, ..session() }, Some(BackendKind::HyprsunsetIpc), "Hyprland"),
+            (Session { hyprland: true, wayland: true, ..session() }, Some(BackendKind::Hyprctl), "Hyprland, hyprsunset not running"),
+            (Session { wayland: true, wl_gammarelay: true, ..session() }, Some(BackendKind::WlGammarelay), "Sway with wl-gammarelay-rs"),
+            (Session { wayland: true, x11: true, wl_gammarelay: true, ..session() }, Some(BackendKind::WlGammarelay), "Sway with XWayland and wl-gammarelay-rs"),
+            (Session { wayland: true, x11: true, ..session() }, None, "Sway with XWayland, RandR there doesn't reach the screen"),
+            (Session { x11: true, ..session() }, Some(BackendKind::Xrandr), "X11"),
+            (Session { wayland: true, ..session() }, None, "Wayland, nothing installed"),
+            (session(), None, "No graphical session"),
+        ];
+
+        for (session, expected_backend, description) in test_cases {
+            assert_eq!(detect_backend(&session), expected_backend, "Detection failed for {}", description);
+        }
+    }
+
+    #[test]
+    fn test_dry_run() {
+        // Programs that don't exist: running any of them would fail
+        let missing_program = "sundial-test-missing-program";
+        let screen_state = ScreenState { temperature: 2800, gamma: 80.0 };
+
+        assert_eq!(dry_run(|output| start_hyprsunset(output, Duration::ZERO)), vec![
+            "[dry-run] Would start hyprsunset unless running: systemctl --user start hyprsunset",
+        ]);
+
+        let hyprctl = HyprctlBackend { hyprctl: Hyprctl { program: missing_program.into() }, startup_timeout: Duration::ZERO };
+        assert_eq!(dry_run(|output| hyprctl.prepare(output)), vec![
+            "[dry-run] Would start hyprsunset unless running: systemctl --user start hyprsunset",
+        ]);
+        assert_eq!(dry_run(|output| hyprctl.apply(&screen_state, output)), vec![
+            "[dry-run] Would run: hyprctl hyprsunset temperature 2800",
+            "[dry-run] Would run: hyprctl hyprsunset gamma 80",
+        ]);
+        assert!(hyprctl.apply(&screen_state, None).is_err());
+
+        let one_shot = OneShotBackend { program: missing_program };
+        assert_eq!(dry_run(|output| one_shot.apply(&screen_state, output)), vec![
+            format!("[dry-run] Would run: {} -m randr -P -O 2800 -b 0.8", missing_program),
+        ]);
+        assert!(one_shot.apply(&screen_state, None).is_err());
+
+        assert_eq!(dry_run(|output| WlGammarelayBackend.apply(&screen_state, output)), vec![
+            "[dry-run] Would run: busctl --user set-property rs.wl-gammarelay / rs.wl.gammarelay Temperature q 2800",
+            "[dry-run] Would run: busctl --user set-property rs.wl-gammarelay / rs.wl.gammarelay Brightness d 0.8",
+        ]);
+
+        assert_eq!(dry_run(|output| XrandrBackend.apply(&screen_state, output)), vec![
+            "[dry-run] Would scale the gamma ramps of every CRTC by 1.000:0.668:0.374 at brightness 0.80",
+        ]);
+    }
+
+    #[test]
+    fn test_dry_run_without_session() {
+        // Neither WAYLAND_DISPLAY, DISPLAY nor HYPRLAND_INSTANCE_SIGNATURE set
+        let mut config = get_test_config();
+        assert_eq!(backend_kind(&config, session, true), Ok(BackendKind::Hyprctl));
+        assert!(backend_kind(&config, session, false).is_err());
+
+        config.output.backend = BackendKind::Redshift;
+        let no_session = || -> Session { panic!("An explicit backend shouldn't look at the session") };
+        assert_eq!(backend_kind(&config, no_session, false), Ok(BackendKind::Redshift));
+
+        let backend = HyprsunsetIpcBackend {
+            ipc: hyprsunset_socket_path(None, None).map(|socket_path| HyprsunsetIpc { socket_path }),
+            startup_timeout: Duration::ZERO,
+        };
+        let screen_state = ScreenState { temperature: 2800, gamma: 80.0 };
+        assert_eq!(dry_run(|output| backend.apply(&screen_state, output)), vec![
+            "[dry-run] Would send to hyprsunset: temperature 2800",
+            "[dry-run] Would send to hyprsunset: gamma 80",
+        ]);
+        assert_eq!(
+            backend.apply(&screen_state, None).unwrap_err().to_string(),
+            "XDG_RUNTIME_DIR is not set, is Hyprland running?",
+        );
+    }
+
+    #[test]
+    fn test_brightness() {
+        let brightness_at = |gamma| brightness(&ScreenState { temperature: 6000, gamma });
+        assert_eq!(brightness_at(80.0), 0.8);
+        assert_eq!(brightness_at(100.0), 1.0);
+        assert_eq!(brightness_at(150.0), 1.0);
+        assert_eq!(brightness_at(5.0), 0.1);
+        assert_eq!(brightness_at(0.0), 0.1);
+    }
+
+    #[test]
+    fn test_gamma_ramp() {
+        assert_eq!(gamma_ramp(4, 1.0), vec![0, 21845, 43690, 65535]);
+        assert_eq!(gamma_ramp(3, 0.5), vec![0, 16384, 32768]);
+        assert_eq!(gamma_ramp(2, 1.5), vec![0, 65535]);
+        assert_eq!(gamma_ramp(1, 1.0), vec![0]);
+
+        // White itself gets warmer, not only the midtones
+        let (red, green, blue) = whitepoint(2800);
+        let full_white = |multiplier: f64| *gamma_ramp(256, multiplier).last().unwrap();
+        assert_eq!(full_white(red), 65535);
+        assert!(full_white(blue) < full_white(green) && full_white(green) < full_white(red));
+
+        // Without any blue at all, the blue channel stays dark throughout
+        let (_, _, blue) = whitepoint(1900);
+        assert!(gamma_ramp(256, blue).iter().all(|&level| level == 0));
+
+        // Brightness scales every level
+        let dimmed = gamma_ramp(256, 0.8);
+        assert_eq!(dimmed[255], 52428);
+        assert_eq!(dimmed[128], (128.0 / 255.0 * 0.8 * 65535.0_f64).round() as u16);
+    }
+
+    #[test]
+    fn test_whitepoint() {
+        let (red, green, blue) = whitepoint(6500);
+        assert_eq!(red, 1.0);
+        assert!(green > 0.95 && blue > 0.95);
+
+        // Warmer means less blue, then less green
+        let (_, green_warm, blue_warm) = whitepoint(2800);
+        let (_, green_neutral, blue_neutral) = whitepoint(5000);
+        assert!(blue_warm < blue_neutral && green_warm < green_neutral);
+        assert_eq!(whitepoint(1000).2, 0.0);
+    }
+}
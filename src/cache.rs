@@ -1,71 +1,193 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::config::Config;
+use crate::config::{Config, LocationConfig};
 use crate::sun_times::SunTimes;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
-pub struct Cache {
+const CACHE_FILE_NAME: &str = "cache.json";
+// Older versions kept one cache-<date>.json file per day
+const DAILY_CACHE_FILE_FORMAT: &str = "cache-%Y-%m-%d.json";
+
+// Sun times for one place and day, as one provider had them
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CacheEntry {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub provider: String,
+    pub date: NaiveDate,
     pub sun_times: SunTimes,
 }
 
+impl CacheEntry {
+    fn is_for(&self, location: &LocationConfig) -> bool {
+        self.latitude == location.latitude && self.longitude == location.longitude
+    }
+
+    fn has_key(&self, location: &LocationConfig, provider: &str, date: NaiveDate) -> bool {
+        self.is_for(location) && self.provider == provider && self.date == date
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct Cache {
+    pub entries: Vec<CacheEntry>,
+    // Last day the days ahead were prefetched, so it's tried once a day
+    #[serde(default)]
+    pub prefetched_on: Option<NaiveDate>,
+}
+
+impl Cache {
+    // Entries of earlier providers win, same as when fetching
+    pub fn get(&self, location: &LocationConfig, providers: &[&str], date: NaiveDate) -> Option<&CacheEntry> {
+        providers.iter().find_map(|provider| {
+            self.entries.iter().find(|entry| entry.has_key(location, provider, date))
+        })
+    }
+
+    // The entry closest to date, at most max_days away, for when date itself
+    // can't be fetched
+    pub fn closest(&self, location: &LocationConfig, providers: &[&str], date: NaiveDate, max_days: u32) -> Option<&CacheEntry> {
+        let days_away = |entry: &CacheEntry| (entry.date - date).num_days().abs();
+
+        self.entries.iter()
+            .filter(|entry| entry.is_for(location) && providers.contains(&entry.provider.as_str()))
+            .filter(|entry| days_away(entry) <= max_days as i64)
+            .min_by_key(|entry| days_away(entry))
+    }
+
+    pub fn insert(&mut self, entry: CacheEntry) {
+        let key = |entry: &CacheEntry| (entry.latitude, entry.longitude, entry.provider.clone(), entry.date);
+        self.entries.retain(|cached| key(cached) != key(&entry));
+        self.entries.push(entry);
+    }
+
+    // Drops days too far in the past to even be fallen back to
+    fn prune(&mut self, today: NaiveDate, max_stale_days: u32) {
+        self.entries.retain(|entry| (today - entry.date).num_days() <= max_stale_days as i64);
+    }
+}
+
 pub fn get_data_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
     let dirs = directories::ProjectDirs::from("", "", "sundial")
         .ok_or("Could not find config directory")?;
 
     let data_dir = dirs.data_dir();
-    std::fs::create_dir_all(&data_dir)?;
+    std::fs::create_dir_all(data_dir)?;
 
     Ok(data_dir.to_path_buf())
 }
 
-
-fn cache_file(data_dir: &PathBuf) -> PathBuf {
-    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
-
-    return data_dir.join(format!("cache-{}.json", today))
+fn cache_file(data_dir: &Path) -> PathBuf {
+    data_dir.join(CACHE_FILE_NAME)
 }
 
-pub fn load_cache(config: &Config, data_dir: &PathBuf) -> Result<Option<Cache>, Box<dyn std::error::Error>> {
+// Empty when caching is disabled or nothing was cached yet
+pub fn load_cache(config: &Config, data_dir: &Path) -> Result<Cache, Box<dyn std::error::Error>> {
     if !config.cache.enabled {
-        return Ok(None);
+        return Ok(Cache::default());
     }
 
     let cache_file = cache_file(data_dir);
 
     if !cache_file.exists() {
-        return Ok(None);
+        return Ok(Cache::default());
     }
 
     let cache_content = std::fs::read_to_string(cache_file)?;
     let cache: Cache = serde_json::from_str(&cache_content)?;
 
-    Ok(Some(cache))
+    Ok(cache)
 }
 
-pub fn persist_to_cache(config: &Config, data_dir: &PathBuf, sun_times: &SunTimes) -> Result<bool, Box<dyn std::error::Error>> {
+// Prunes expired entries, then replaces the cache file in one go: written
+// next to it first and renamed over it, so it's never left half written
+pub fn persist_to_cache(config: &Config, data_dir: &Path, cache: &mut Cache, today: NaiveDate) -> Result<bool, Box<dyn std::error::Error>> {
     if !config.cache.enabled {
         return Ok(false)
     }
 
-    std::fs::remove_dir_all(&data_dir)?;
-    std::fs::create_dir_all(&data_dir)?;
+    cache.prune(today, config.cache.max_stale_days);
 
     let cache_file = cache_file(data_dir);
-    let cache = Cache { sun_times: sun_times.clone() };
-    let cache_content = serde_json::to_string(&cache)?;
+    let temporary_file = cache_file.with_extension("json.tmp");
+    let cache_content = serde_json::to_string(cache)?;
 
-    std::fs::write(cache_file, cache_content)?;
+    std::fs::write(&temporary_file, cache_content)?;
+    std::fs::rename(&temporary_file, &cache_file)?;
+
+    prune_daily_cache_files(data_dir, today)?;
 
     Ok(true)
 }
 
+// The date of a daily cache file, None for anything that isn't one
+fn daily_cache_file_date(path: &Path) -> Option<NaiveDate> {
+    let file_name = path.file_name()?.to_str()?;
+
+    NaiveDate::parse_from_str(file_name, DAILY_CACHE_FILE_FORMAT).ok()
+}
+
+// Removes expired daily cache files and nothing else: data_dir holds more
+// than the cache, and might be shared with other programs
+fn prune_daily_cache_files(data_dir: &Path, today: NaiveDate) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut removed = 0;
+
+    for entry in std::fs::read_dir(data_dir)? {
+        let path = entry?.path();
+
+        if path.is_file() && daily_cache_file_date(&path).is_some_and(|date| date < today) {
+            std::fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+// Removes cache files only, leaving anything else in data_dir alone. That
+// includes the per-day cache-<date>.json files of older versions
+pub fn clear_cache(data_dir: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut removed = 0;
+
+    for entry in std::fs::read_dir(data_dir)? {
+        let path = entry?.path();
+        let is_cache_file = path.file_name().is_some_and(|name| name == CACHE_FILE_NAME) || daily_cache_file_date(&path).is_some();
+
+        if path.is_file() && is_cache_file {
+            std::fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::NaiveTime;
+    use chrono::{TimeZone, Utc};
+    use crate::sun_times::Twilight;
     use crate::config::get_test_config;
 
+    fn entry(location: &LocationConfig, provider: &str, day: u32, sunrise_hour: u32) -> CacheEntry {
+        CacheEntry {
+            latitude: location.latitude,
+            longitude: location.longitude,
+            provider: provider.to_string(),
+            date: NaiveDate::from_ymd_opt(2025, 8, day).unwrap(),
+            sun_times: SunTimes::Regular {
+                sunrise: Utc.with_ymd_and_hms(2025, 8, day, sunrise_hour, 30, 0).unwrap(),
+                sunset: Utc.with_ymd_and_hms(2025, 8, day, 18, 45, 0).unwrap(),
+                twilight: Twilight::default(),
+            },
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 8, day).unwrap()
+    }
+
     #[test]
     fn test_cache_enabled_persist_and_load() {
         let temp_dir = std::env::temp_dir().join("sundial_test_cache_enabled_persist_load");
@@ -74,22 +196,47 @@ mod tests {
 
         let mut config = get_test_config();
         config.cache.enabled = true;
-
-        let sun_times = SunTimes {
-            sunrise: NaiveTime::from_hms_opt(6, 30, 0).unwrap(),
-            sunset: NaiveTime::from_hms_opt(18, 45, 0).unwrap(),
-        };
+        config.cache.max_stale_days = 1;
+        let location = config.location.clone();
 
         // no cache file exists
         let load_result = load_cache(&config, &temp_dir);
-        assert_eq!(load_result.unwrap(), None);
+        assert_eq!(load_result.unwrap(), Cache::default());
 
-        let persist_result = persist_to_cache(&config, &temp_dir, &sun_times);
+        let mut cache = Cache::default();
+        for day in 15..=20 {
+            cache.insert(entry(&location, "sunrisesunset.io", day, 6));
+        }
+        let persist_result = persist_to_cache(&config, &temp_dir, &mut cache, date(17));
         assert!(persist_result.unwrap());
+        assert!(!temp_dir.join("cache.json.tmp").exists());
 
-        let load_result = load_cache(&config, &temp_dir);
-        let loaded_cache = load_result.unwrap().unwrap();
-        assert_eq!(loaded_cache.sun_times, sun_times);
+        // The 15th is more than max_stale_days old
+        let loaded_cache = load_cache(&config, &temp_dir).unwrap();
+        assert_eq!(loaded_cache.entries.len(), 5);
+        assert_eq!(loaded_cache.get(&location, &["sunrisesunset.io"], date(15)), None);
+        assert_eq!(loaded_cache.get(&location, &["sunrisesunset.io"], date(16)), Some(&entry(&location, "sunrisesunset.io", 16, 6)));
+        assert_eq!(loaded_cache.get(&location, &["sunrisesunset.io"], date(20)), Some(&entry(&location, "sunrisesunset.io", 20, 6)));
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_prefetched_on() {
+        let temp_dir = std::env::temp_dir().join("sundial_test_cache_prefetched_on");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let mut config = get_test_config();
+        config.cache.enabled = true;
+
+        // Written before sundial kept track of prefetching
+        std::fs::write(temp_dir.join(CACHE_FILE_NAME), r#"{"entries":[]}"#).unwrap();
+        assert_eq!(load_cache(&config, &temp_dir).unwrap().prefetched_on, None);
+
+        let mut cache = Cache { prefetched_on: Some(date(17)), ..Cache::default() };
+        persist_to_cache(&config, &temp_dir, &mut cache, date(17)).unwrap();
+        assert_eq!(load_cache(&config, &temp_dir).unwrap().prefetched_on, Some(date(17)));
 
         std::fs::remove_dir_all(&temp_dir).unwrap();
     }
@@ -103,16 +250,112 @@ mod tests {
         let mut config = get_test_config();
         config.cache.enabled = false;
 
-        let sun_times = SunTimes {
-            sunrise: NaiveTime::from_hms_opt(6, 30, 0).unwrap(),
-            sunset: NaiveTime::from_hms_opt(18, 45, 0).unwrap(),
-        };
+        let mut cache = Cache::default();
+        cache.insert(entry(&config.location, "calculated", 17, 6));
 
-        let persist_result = persist_to_cache(&config, &temp_dir, &sun_times);
-        assert_eq!(persist_result.unwrap(), false);
+        let persist_result = persist_to_cache(&config, &temp_dir, &mut cache, date(17));
+        assert!(!persist_result.unwrap());
 
         let load_result = load_cache(&config, &temp_dir);
-        assert_eq!(load_result.unwrap(), None);
+        assert_eq!(load_result.unwrap(), Cache::default());
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_persist_to_cache_leaves_other_files_alone() {
+        let temp_dir = std::env::temp_dir().join("sundial_test_persist_to_cache_leaves_other_files_alone");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(temp_dir.join("cache-2025-08-01.json")).unwrap();
+
+        let mut config = get_test_config();
+        config.cache.enabled = true;
+
+        let unrelated_files = ["notes.txt", "override.json", "cache-backup.json"];
+        // Our own legacy files, but not older than today
+        let current_files = ["cache-2025-08-17.json", "cache-2025-08-20.json"];
+        for file_name in unrelated_files.iter().chain(&current_files) {
+            std::fs::write(temp_dir.join(file_name), "keep me").unwrap();
+        }
+        std::fs::write(temp_dir.join("cache-2025-08-16.json"), "{}").unwrap();
+
+        let mut cache = Cache::default();
+        cache.insert(entry(&config.location, "calculated", 17, 6));
+        persist_to_cache(&config, &temp_dir, &mut cache, date(17)).unwrap();
+
+        // Only our own expired file is gone, directories are never touched
+        assert!(!temp_dir.join("cache-2025-08-16.json").exists());
+        for file_name in unrelated_files {
+            assert_eq!(std::fs::read_to_string(temp_dir.join(file_name)).unwrap(), "keep me", "{} was touched", file_name);
+        }
+        for file_name in current_files {
+            assert!(temp_dir.join(file_name).exists(), "{} is not older than today, but was removed", file_name);
+        }
+        assert!(temp_dir.join("cache-2025-08-01.json").is_dir());
+        assert!(temp_dir.join("cache.json").exists());
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_keys() {
+        let berlin = get_test_config().location;
+        let lisbon = LocationConfig { latitude: 38.72, longitude: -9.14 };
+
+        let mut cache = Cache::default();
+        cache.insert(entry(&berlin, "sunrisesunset.io", 17, 4));
+        cache.insert(entry(&berlin, "calculated", 17, 5));
+        cache.insert(entry(&lisbon, "sunrisesunset.io", 17, 6));
+        // Replaces the first one
+        cache.insert(entry(&berlin, "sunrisesunset.io", 17, 7));
+        assert_eq!(cache.entries.len(), 3);
+
+        let providers = ["sunrisesunset.io", "calculated"];
+        assert_eq!(cache.get(&berlin, &providers, date(17)), Some(&entry(&berlin, "sunrisesunset.io", 17, 7)));
+        assert_eq!(cache.get(&berlin, &["calculated"], date(17)), Some(&entry(&berlin, "calculated", 17, 5)));
+        assert_eq!(cache.get(&lisbon, &providers, date(17)), Some(&entry(&lisbon, "sunrisesunset.io", 17, 6)));
+        assert_eq!(cache.get(&lisbon, &["calculated"], date(17)), None);
+        assert_eq!(cache.get(&berlin, &providers, date(18)), None);
+    }
+
+    #[test]
+    fn test_closest() {
+        let berlin = get_test_config().location;
+        let lisbon = LocationConfig { latitude: 38.72, longitude: -9.14 };
+
+        let mut cache = Cache::default();
+        cache.insert(entry(&berlin, "sunrisesunset.io", 12, 6));
+        cache.insert(entry(&berlin, "sunrisesunset.io", 14, 6));
+        cache.insert(entry(&berlin, "fixed", 16, 6));
+        cache.insert(entry(&lisbon, "sunrisesunset.io", 16, 6));
+
+        let providers = ["sunrisesunset.io"];
+        assert_eq!(cache.closest(&berlin, &providers, date(17), 3), Some(&entry(&berlin, "sunrisesunset.io", 14, 6)));
+        assert_eq!(cache.closest(&berlin, &providers, date(13), 3).map(|entry| entry.date), Some(date(12)));
+        assert_eq!(cache.closest(&berlin, &providers, date(17), 2), None);
+        assert_eq!(cache.closest(&lisbon, &["fixed"], date(17), 7), None);
+    }
+
+    #[test]
+    fn test_clear_cache() {
+        let temp_dir = std::env::temp_dir().join("sundial_test_clear_cache");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let mut config = get_test_config();
+        config.cache.enabled = true;
+
+        let mut cache = Cache::default();
+        cache.insert(entry(&config.location, "calculated", 17, 6));
+        persist_to_cache(&config, &temp_dir, &mut cache, date(17)).unwrap();
+        std::fs::write(temp_dir.join("cache-2025-08-18.json"), "{}").unwrap();
+        std::fs::write(temp_dir.join("cache-backup.json"), "keep me").unwrap();
+        std::fs::write(temp_dir.join("notes.txt"), "keep me").unwrap();
+
+        assert_eq!(clear_cache(&temp_dir).unwrap(), 2);
+        assert_eq!(load_cache(&config, &temp_dir).unwrap(), Cache::default());
+        assert!(temp_dir.join("notes.txt").exists());
+        assert!(temp_dir.join("cache-backup.json").exists());
 
         std::fs::remove_dir_all(&temp_dir).unwrap();
     }
@@ -0,0 +1,186 @@
+use chrono::{Duration, NaiveTime};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Sets hyprsunset's temperature and gamma based on local sunrise and sunset times")]
+pub struct Cli {
+    /// Path to the config file, instead of ~/.config/sundial/config.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Print the commands that would change the screen instead of running them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Don't show a desktop notification when something fails
+    #[arg(long, global = true)]
+    pub no_notify: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Apply the current screen state once (default)
+    Run,
+    /// Stay resident and apply screen states as they change
+    Daemon,
+    /// Print today's sun times and the current screen state
+    Status,
+    /// Print the screen state for a given local time, without applying it
+    Preview {
+        /// Local time, as HH:MM
+        #[arg(long, value_parser = parse_time)]
+        at: NaiveTime,
+    },
+    /// Hold a screen state, ignoring the schedule until it expires
+    Override {
+        /// Temperature in Kelvin, the day temperature by default
+        #[arg(long)]
+        temperature: Option<u32>,
+        /// Gamma in percent, the day gamma by default
+        #[arg(long)]
+        gamma: Option<f64>,
+        /// How long to hold it, e.g. 2h, 90m or 1h30m; until `sundial resume` by default
+        #[arg(long = "for", value_name = "DURATION", value_parser = parse_duration)]
+        duration: Option<Duration>,
+    },
+    /// Hold the current screen state, ignoring the schedule until it expires
+    Pause {
+        /// How long to hold it, e.g. 2h, 90m or 1h30m; until `sundial resume` by default
+        #[arg(long = "for", value_name = "DURATION", value_parser = parse_duration)]
+        duration: Option<Duration>,
+    },
+    /// End an override or pause, fading back into the schedule
+    Resume,
+    /// Manage the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Manage cached sun times
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Write the default config file
+    Init {
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the config in use
+    Show,
+    /// Check the config file for errors
+    Validate,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// Print the cached sun times
+    Show,
+    /// Remove all cached sun times
+    Clear,
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map_err(|_| format!("'{}' is not a valid time, expected HH:MM", value))
+}
+
+// Whole numbers followed by d, h, m or s, e.g. 2h or 1h30m
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("'{}' is not a valid duration, expected e.g. 2h, 90m or 1h30m", value);
+    let too_long = || format!("'{}' is too long a duration", value);
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+
+    for character in value.chars() {
+        if character.is_ascii_digit() {
+            number.push(character);
+            continue;
+        }
+
+        if number.is_empty() {
+            return Err(invalid());
+        }
+        // Only digits are left, so this can only overflow
+        let amount: i64 = number.parse().map_err(|_| too_long())?;
+        let part = match character {
+            'd' => Duration::try_days(amount),
+            'h' => Duration::try_hours(amount),
+            'm' => Duration::try_minutes(amount),
+            's' => Duration::try_seconds(amount),
+            _ => return Err(invalid()),
+        };
+        duration = part.and_then(|part| duration.checked_add(&part)).ok_or_else(too_long)?;
+        number.clear();
+    }
+
+    if !number.is_empty() || duration <= Duration::zero() {
+        return Err(invalid());
+    }
+
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subcommands() {
+        let cli = Cli::try_parse_from(["sundial"]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(cli.config.is_none());
+        assert!(!cli.dry_run);
+
+        let cli = Cli::try_parse_from(["sundial", "daemon", "--dry-run", "--no-notify"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Daemon)));
+        assert!(cli.dry_run);
+        assert!(cli.no_notify);
+
+        let cli = Cli::try_parse_from(["sundial", "preview", "--at", "21:30", "--config", "/tmp/sundial.toml"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Preview { at }) if at == NaiveTime::from_hms_opt(21, 30, 0).unwrap()));
+        assert_eq!(cli.config, Some(PathBuf::from("/tmp/sundial.toml")));
+
+        let cli = Cli::try_parse_from(["sundial", "config", "init", "--force"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Config { action: ConfigCommand::Init { force: true } })));
+
+        let cli = Cli::try_parse_from(["sundial", "override", "--temperature", "6500", "--gamma", "100", "--for", "2h"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Override { temperature: Some(6500), gamma: Some(100.0), duration: Some(duration) }) if duration == Duration::hours(2),
+        ));
+
+        let cli = Cli::try_parse_from(["sundial", "pause"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Pause { duration: None })));
+
+        assert!(Cli::try_parse_from(["sundial", "preview", "--at", "25:00"]).is_err());
+        assert!(Cli::try_parse_from(["sundial", "pause", "--for", "forever"]).is_err());
+        assert!(Cli::try_parse_from(["sundial", "cache", "wipe"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("2h"), Ok(Duration::hours(2)));
+        assert_eq!(parse_duration("90m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::days(1)));
+        assert_eq!(parse_duration("45s"), Ok(Duration::seconds(45)));
+
+        for invalid in ["", "2", "h", "0m", "2x", "1.5h"] {
+            assert!(parse_duration(invalid).is_err(), "{} should be invalid", invalid);
+        }
+
+        for too_long in ["9999999999999d", "99999999999999999999s", "106751991167300d1d"] {
+            assert_eq!(parse_duration(too_long), Err(format!("'{}' is too long a duration", too_long)));
+        }
+    }
+}
@@ -1,102 +1,812 @@
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike, Weekday};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::sun_times::SolarEvent;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
     pub location: LocationConfig,
     pub screen: ScreenConfig,
     pub cache: CacheConfig,
+    #[serde(default)]
+    pub sun_times: SunTimesConfig,
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    // Screen settings for some days only, picked by the rules below
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profile_rules: Vec<ProfileRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holidays: Option<HolidaysConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ProfileConfig {
+    // Overrides for [screen]
+    #[serde(default)]
+    pub screen: toml::Table,
 }
 
+// Every criterion given has to match
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ProfileRule {
+    pub profile: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weekdays: Vec<Weekday>,
+    // Inclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dates: Vec<NaiveDate>,
+}
+
+impl ProfileRule {
+    fn matches(&self, date: NaiveDate) -> bool {
+        (self.weekdays.is_empty() || self.weekdays.contains(&date.weekday()))
+            && self.from.is_none_or(|from| date >= from)
+            && self.to.is_none_or(|to| date <= to)
+            && (self.dates.is_empty() || self.dates.contains(&date))
+    }
+}
+
+// Days that take precedence over every rule
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HolidaysConfig {
+    pub profile: String,
+    pub dates: Vec<NaiveDate>,
+}
+
+// Decimal degrees, negative for south/west. Strings are parsed, so both
+// "52°33'N" and "13.39E" work too
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LocationConfig {
-    pub latitude: String,
-    pub longitude: String,
+    #[serde(deserialize_with = "latitude")]
+    pub latitude: f64,
+    #[serde(deserialize_with = "longitude")]
+    pub longitude: f64,
 }
 
+// Temperatures are in Kelvin and gammas in percent. Older configs quoted
+// them ("6000"), so strings holding a number are still accepted
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ScreenConfig {
-    pub day_temperature: String,
-    pub day_gamma: String,
-    pub night_temperature: String,
-    pub night_gamma: String,
+    #[serde(deserialize_with = "number_or_string")]
+    pub day_temperature: u32,
+    #[serde(deserialize_with = "number_or_string")]
+    pub day_gamma: f64,
+    #[serde(deserialize_with = "number_or_string")]
+    pub night_temperature: u32,
+    #[serde(deserialize_with = "number_or_string")]
+    pub night_gamma: f64,
     pub fade_duration_in_minutes: i64,
+    // Space the temperature is interpolated in while fading
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    // Warming up to day settings
+    #[serde(default = "default_morning")]
+    pub morning: TransitionConfig,
+    // Cooling down to night settings
+    #[serde(default = "default_evening")]
+    pub evening: TransitionConfig,
+    // Replaces the day/night settings and the morning and evening transitions
+    // above when given, except during polar days and nights
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keyframes: Vec<KeyframeConfig>,
+}
+
+// A screen state reached at a solar event or at a local clock time, faded
+// into from the previous keyframe
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct KeyframeConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<SolarEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<NaiveTime>,
+    #[serde(default)]
+    pub offset_in_minutes: i64,
+    #[serde(deserialize_with = "number_or_string")]
+    pub temperature: u32,
+    #[serde(deserialize_with = "number_or_string")]
+    pub gamma: f64,
+    // Overrides screen.fade_duration_in_minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_in_minutes: Option<i64>,
+    #[serde(default)]
+    pub align: Alignment,
+    #[serde(default)]
+    pub curve: Curve,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TransitionConfig {
+    // Solar event the fade is anchored to
+    pub anchor: SolarEvent,
+    // Whether the fade starts, ends or is centered at the anchor
+    pub align: Alignment,
+    // Overrides screen.fade_duration_in_minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_in_minutes: Option<i64>,
+    // Moves the anchor, negative is earlier
+    #[serde(default)]
+    pub offset_in_minutes: i64,
+    // How the fade progresses from one setting to the other
+    #[serde(default)]
+    pub curve: Curve,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Alignment {
+    Start,
+    #[default]
+    End,
+    Center,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    #[default]
+    Linear,
+    // Eases in and out
+    Smoothstep,
+    // Like smoothstep, but flatter at both ends
+    Sigmoid,
+    // Slow at first, fast towards the end
+    Exponential,
+    // Follows the sun's elevation between the start and the end of the fade
+    SolarElevation,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    #[default]
+    Kelvin,
+    // Reciprocal megakelvin (1,000,000 / K): equal steps look roughly equally
+    // large to the eye, where Kelvin steps are barely noticeable when cool
+    Mired,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct OutputConfig {
+    // What screen states are applied with
+    pub backend: BackendKind,
+    // How long to wait for hyprsunset to accept commands after starting it
+    pub startup_timeout_in_seconds: u64,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            backend: BackendKind::default(),
+            startup_timeout_in_seconds: STARTUP_TIMEOUT_IN_SECONDS,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    // Picks one of the below based on the session
+    #[default]
+    Auto,
+    // Runs `hyprctl hyprsunset ...`
+    Hyprctl,
+    // Writes to hyprsunset's socket directly
+    HyprsunsetIpc,
+    // Sets wl-gammarelay-rs' properties over D-Bus
+    WlGammarelay,
+    // One-shot runs of gammastep or redshift
+    Gammastep,
+    Redshift,
+    // Gamma ramps through X11 RandR
+    Xrandr,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CacheConfig {
     pub enabled: bool,
+    // Days after today fetched along with it, to last through some time offline
+    #[serde(default = "default_prefetch_days")]
+    pub prefetch_days: u32,
+    // How old cached sun times may be to still be used, shifted to today,
+    // when fetching fails
+    #[serde(default = "default_max_stale_days")]
+    pub max_stale_days: u32,
+}
+
+fn default_prefetch_days() -> u32 {
+    PREFETCH_DAYS
+}
+
+fn default_max_stale_days() -> u32 {
+    MAX_STALE_DAYS
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct SunTimesConfig {
+    // Providers are tried in order until one of them succeeds
+    pub providers: Vec<ProviderKind>,
+    // Local times used by the "fixed" provider
+    pub fixed_sunrise: NaiveTime,
+    pub fixed_sunset: NaiveTime,
+    // What to do on days the sun never rises or never sets
+    pub polar: PolarBehavior,
 }
 
-pub const BERLIN_LAT: &str = "52.56";
-pub const BERLIN_LON: &str = "13.39";
-const DAY_TEMPERATURE: &str = "6000";
-const DAY_GAMMA: &str = "100";
-const NIGHT_TEMPERATURE: &str = "2800";
-const NIGHT_GAMMA: &str = "80";
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PolarBehavior {
+    // Stay on day settings during polar day and on night settings during polar night
+    #[default]
+    FollowSun,
+    // Use fixed_sunrise and fixed_sunset instead
+    Fixed,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum ProviderKind {
+    #[serde(rename = "sunrisesunset.io")]
+    SunriseSunsetIo,
+    #[serde(rename = "sunrise-sunset.org")]
+    SunriseSunsetOrg,
+    #[serde(rename = "calculated")]
+    Calculated,
+    #[serde(rename = "fixed")]
+    Fixed,
+}
+
+impl Default for SunTimesConfig {
+    fn default() -> Self {
+        SunTimesConfig {
+            providers: vec![ProviderKind::SunriseSunsetIo, ProviderKind::Calculated],
+            fixed_sunrise: NaiveTime::from_hms_opt(FIXED_SUNRISE.0, FIXED_SUNRISE.1, 0).unwrap(),
+            fixed_sunset: NaiveTime::from_hms_opt(FIXED_SUNSET.0, FIXED_SUNSET.1, 0).unwrap(),
+            polar: PolarBehavior::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    pub mode: ScheduleMode,
+    // Local times day and night settings are reached at in clock mode,
+    // instead of sunrise and sunset
+    pub day_start: NaiveTime,
+    pub night_start: NaiveTime,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleMode {
+    #[default]
+    Sun,
+    // Ignores the sun: no sun times are fetched or calculated at all
+    Clock,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        ScheduleConfig {
+            mode: ScheduleMode::default(),
+            day_start: NaiveTime::from_hms_opt(DAY_START.0, DAY_START.1, 0).unwrap(),
+            night_start: NaiveTime::from_hms_opt(NIGHT_START.0, NIGHT_START.1, 0).unwrap(),
+        }
+    }
+}
+
+pub const BERLIN_LAT: f64 = 52.56;
+pub const BERLIN_LON: f64 = 13.39;
+const DAY_TEMPERATURE: u32 = 6000;
+const DAY_GAMMA: f64 = 100.0;
+const NIGHT_TEMPERATURE: u32 = 2800;
+const NIGHT_GAMMA: f64 = 80.0;
 const FADE_DURATION_IN_MINUTES: i64 = 60;
 const CACHE_ENABLED: bool = true;
+const PREFETCH_DAYS: u32 = 7;
+const MAX_STALE_DAYS: u32 = 3;
+const FIXED_SUNRISE: (u32, u32) = (7, 0);
+const FIXED_SUNSET: (u32, u32) = (19, 0);
+const DAY_START: (u32, u32) = (7, 0);
+const NIGHT_START: (u32, u32) = (19, 0);
+const STARTUP_TIMEOUT_IN_SECONDS: u64 = 5;
+
+const TEMPERATURE_RANGE: std::ops::RangeInclusive<u32> = 1000..=20000;
+const GAMMA_RANGE: std::ops::RangeInclusive<f64> = 0.0..=200.0;
+const FADE_DURATION_RANGE: std::ops::RangeInclusive<i64> = 0..=720;
+const FADE_OFFSET_RANGE: std::ops::RangeInclusive<i64> = -720..=720;
+const LATITUDE_RANGE: std::ops::RangeInclusive<f64> = -90.0..=90.0;
+const LONGITUDE_RANGE: std::ops::RangeInclusive<f64> = -180.0..=180.0;
+const STARTUP_TIMEOUT_RANGE: std::ops::RangeInclusive<u64> = 1..=300;
+const PREFETCH_DAYS_RANGE: std::ops::RangeInclusive<u32> = 0..=31;
+const MAX_STALE_DAYS_RANGE: std::ops::RangeInclusive<u32> = 0..=31;
+
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+fn default_morning() -> TransitionConfig {
+    TransitionConfig { anchor: SolarEvent::Sunrise, align: Alignment::End, duration_in_minutes: None, offset_in_minutes: 0, curve: Curve::Linear }
+}
+
+fn default_evening() -> TransitionConfig {
+    TransitionConfig { anchor: SolarEvent::Sunset, align: Alignment::End, duration_in_minutes: None, offset_in_minutes: 0, curve: Curve::Linear }
+}
 
 pub fn get_config_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
     let dirs = directories::ProjectDirs::from("", "", "sundial")
         .ok_or("Could not find config directory")?;
 
     let config_dir = dirs.config_dir();
-    std::fs::create_dir_all(&config_dir)?;
+    std::fs::create_dir_all(config_dir)?;
 
     Ok(config_dir.to_path_buf())
 }
 
-pub fn load_config(config_dir: PathBuf) -> Result<Config, Box<dyn std::error::Error>> {
-    let config_file = config_dir.join("config.toml");
-
+// Only the config in the default location is created when it's missing, a
+// missing --config file is more likely a typo
+pub fn load_config(config_file: &Path, create_missing: bool) -> Result<Config, Box<dyn std::error::Error>> {
     if config_file.exists() {
         let config_content = std::fs::read_to_string(config_file)?;
-        let config: Config = toml::from_str(&config_content)?;
+        let config: Config = toml::from_str(&config_content)
+            .map_err(|error| format!("Invalid config in {}: {}", config_file.display(), error))?;
+        validate_config(&config)
+            .map_err(|error| format!("Invalid config in {}:\n{}", config_file.display(), error))?;
 
         return Ok(config);
     }
 
-    let default_config = Config {
+    if !create_missing {
+        return Err(format!("{} does not exist, create it with `sundial config init`", config_file.display()).into());
+    }
+
+    write_default_config(config_file)
+}
+
+pub fn write_default_config(config_file: &Path) -> Result<Config, Box<dyn std::error::Error>> {
+    let default_config = default_config();
+
+    if let Some(parent) = config_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let config_toml = toml::to_string(&default_config)?;
+    std::fs::write(config_file, config_toml)?;
+
+    Ok(default_config)
+}
+
+pub fn default_config() -> Config {
+    Config {
         location: LocationConfig {
-            latitude: BERLIN_LAT.to_string(),
-            longitude: BERLIN_LON.to_string(),
+            latitude: BERLIN_LAT,
+            longitude: BERLIN_LON,
         },
         screen: ScreenConfig {
-            day_temperature: DAY_TEMPERATURE.to_string(),
-            day_gamma: DAY_GAMMA.to_string(),
-            night_temperature: NIGHT_TEMPERATURE.to_string(),
-            night_gamma: NIGHT_GAMMA.to_string(),
+            day_temperature: DAY_TEMPERATURE,
+            day_gamma: DAY_GAMMA,
+            night_temperature: NIGHT_TEMPERATURE,
+            night_gamma: NIGHT_GAMMA,
             fade_duration_in_minutes: FADE_DURATION_IN_MINUTES,
+            interpolation: Interpolation::Kelvin,
+            morning: default_morning(),
+            evening: default_evening(),
+            keyframes: Vec::new(),
         },
         cache: CacheConfig {
             enabled: CACHE_ENABLED,
+            prefetch_days: PREFETCH_DAYS,
+            max_stale_days: MAX_STALE_DAYS,
         },
+        sun_times: SunTimesConfig::default(),
+        schedule: ScheduleConfig::default(),
+        output: OutputConfig::default(),
+        profiles: BTreeMap::new(),
+        profile_rules: Vec::new(),
+        holidays: None,
+    }
+}
+
+// Returns every problem found, one per line, so they can all be fixed at once
+pub fn validate_config(config: &Config) -> Result<(), String> {
+    let mut errors = Vec::new();
+    let location = &config.location;
+
+    for (field, value, range) in [("latitude", location.latitude, LATITUDE_RANGE), ("longitude", location.longitude, LONGITUDE_RANGE)] {
+        if !range.contains(&value) {
+            errors.push(format!("location.{} must be between {} and {}, got {}", field, range.start(), range.end(), value));
+        }
+    }
+
+    let startup_timeout = config.output.startup_timeout_in_seconds;
+    if !STARTUP_TIMEOUT_RANGE.contains(&startup_timeout) {
+        errors.push(format!(
+            "output.startup_timeout_in_seconds must be between {} and {}, got {}",
+            STARTUP_TIMEOUT_RANGE.start(), STARTUP_TIMEOUT_RANGE.end(), startup_timeout,
+        ));
+    }
+
+    let cache_days = [
+        ("prefetch_days", config.cache.prefetch_days, PREFETCH_DAYS_RANGE),
+        ("max_stale_days", config.cache.max_stale_days, MAX_STALE_DAYS_RANGE),
+    ];
+    for (field, days, range) in cache_days {
+        if !range.contains(&days) {
+            errors.push(format!("cache.{} must be between {} and {}, got {}", field, range.start(), range.end(), days));
+        }
+    }
+
+    validate_screen(&config.screen, "screen", config.schedule.mode, &mut errors);
+
+    for name in config.profiles.keys() {
+        match profile_screen(config, name) {
+            Ok(screen) => validate_screen(&screen, &format!("profiles.{}.screen", name), config.schedule.mode, &mut errors),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    let rule_profiles = config.profile_rules.iter().enumerate()
+        .map(|(index, rule)| (format!("profile_rules[{}].profile", index), &rule.profile));
+    let holiday_profile = config.holidays.iter().map(|holidays| ("holidays.profile".to_string(), &holidays.profile));
+    for (field, profile) in rule_profiles.chain(holiday_profile) {
+        if !config.profiles.contains_key(profile) {
+            errors.push(format!("{} refers to an unknown profile \"{}\"", field, profile));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+// For screen states given on the command line, e.g. by `sundial override`
+pub fn validate_screen_state(temperature: u32, gamma: f64) -> Result<(), String> {
+    if !TEMPERATURE_RANGE.contains(&temperature) {
+        return Err(format!("temperature must be between {} and {} K, got {}", TEMPERATURE_RANGE.start(), TEMPERATURE_RANGE.end(), temperature));
+    }
+    if !GAMMA_RANGE.contains(&gamma) {
+        return Err(format!("gamma must be between {} and {} %, got {}", GAMMA_RANGE.start(), GAMMA_RANGE.end(), gamma));
+    }
+
+    Ok(())
+}
+
+fn validate_screen(screen: &ScreenConfig, prefix: &str, mode: ScheduleMode, errors: &mut Vec<String>) {
+    let mut temperatures = vec![
+        (format!("{}.day_temperature", prefix), screen.day_temperature),
+        (format!("{}.night_temperature", prefix), screen.night_temperature),
+    ];
+    let mut gammas = vec![
+        (format!("{}.day_gamma", prefix), screen.day_gamma),
+        (format!("{}.night_gamma", prefix), screen.night_gamma),
+    ];
+    let mut durations = vec![(format!("{}.fade_duration_in_minutes", prefix), Some(screen.fade_duration_in_minutes))];
+    let mut offsets = Vec::new();
+
+    for (name, transition) in [("morning", &screen.morning), ("evening", &screen.evening)] {
+        durations.push((format!("{}.{}.duration_in_minutes", prefix, name), transition.duration_in_minutes));
+        offsets.push((format!("{}.{}.offset_in_minutes", prefix, name), transition.offset_in_minutes));
+    }
+
+    for (index, keyframe) in screen.keyframes.iter().enumerate() {
+        let name = format!("{}.keyframes[{}]", prefix, index);
+        match (keyframe.anchor, keyframe.time) {
+            (None, None) => errors.push(format!("{} needs either an anchor or a time", name)),
+            (Some(_), Some(_)) => errors.push(format!("{} can't have both an anchor and a time", name)),
+            (Some(_), None) if mode == ScheduleMode::Clock => {
+                errors.push(format!("{} needs a time instead of an anchor, schedule.mode is \"clock\"", name));
+            },
+            _ => {}
+        }
+        temperatures.push((format!("{}.temperature", name), keyframe.temperature));
+        gammas.push((format!("{}.gamma", name), keyframe.gamma));
+        durations.push((format!("{}.duration_in_minutes", name), keyframe.duration_in_minutes));
+        offsets.push((format!("{}.offset_in_minutes", name), keyframe.offset_in_minutes));
+    }
+
+    for (field, temperature) in temperatures {
+        if !TEMPERATURE_RANGE.contains(&temperature) {
+            errors.push(format!(
+                "{} must be between {} and {} K, got {}",
+                field, TEMPERATURE_RANGE.start(), TEMPERATURE_RANGE.end(), temperature,
+            ));
+        }
+    }
+
+    for (field, gamma) in gammas {
+        if !GAMMA_RANGE.contains(&gamma) {
+            errors.push(format!(
+                "{} must be between {} and {} %, got {}",
+                field, GAMMA_RANGE.start(), GAMMA_RANGE.end(), gamma,
+            ));
+        }
+    }
+
+    // Durations left out fall back to fade_duration_in_minutes
+    for (field, duration) in durations {
+        if let Some(duration) = duration.filter(|duration| !FADE_DURATION_RANGE.contains(duration)) {
+            errors.push(format!(
+                "{} must be between {} and {}, got {}",
+                field, FADE_DURATION_RANGE.start(), FADE_DURATION_RANGE.end(), duration,
+            ));
+        }
+    }
+
+    for (field, offset) in offsets {
+        if !FADE_OFFSET_RANGE.contains(&offset) {
+            errors.push(format!(
+                "{} must be between {} and {}, got {}",
+                field, FADE_OFFSET_RANGE.start(), FADE_OFFSET_RANGE.end(), offset,
+            ));
+        }
+    }
+
+    for (first, second) in overlapping_keyframes(screen) {
+        errors.push(format!("{}.keyframes[{}] and {}.keyframes[{}] have overlapping fades", prefix, first, prefix, second));
+    }
+}
+
+// Pairs of keyframes whose fades overlap. Only clock keyframes and keyframes
+// anchored to the same solar event can be compared up front, how far apart
+// different events are depends on the day and place
+fn overlapping_keyframes(screen: &ScreenConfig) -> Vec<(usize, usize)> {
+    const DAY_IN_SECONDS: i64 = 24 * 60 * 60;
+
+    // In seconds from midnight or from the solar event
+    let fade_window = |keyframe: &KeyframeConfig| {
+        let duration = keyframe.duration_in_minutes.unwrap_or(screen.fade_duration_in_minutes) * 60;
+        let time = keyframe.time.map_or(0, |time| time.num_seconds_from_midnight() as i64);
+        let anchor = time + keyframe.offset_in_minutes * 60;
+        match keyframe.align {
+            Alignment::Start => (anchor, anchor + duration),
+            Alignment::End => (anchor - duration, anchor),
+            Alignment::Center => (anchor - duration / 2, anchor + duration / 2),
+        }
     };
+    let overlap = |(start, end): (i64, i64), (other_start, other_end): (i64, i64)| start < other_end && other_start < end;
 
-    let config_toml = toml::to_string(&default_config)?;
-    std::fs::write(config_file, config_toml)?;
+    let mut overlapping = Vec::new();
+    for (first, keyframe) in screen.keyframes.iter().enumerate() {
+        for (second, other) in screen.keyframes.iter().enumerate().skip(first + 1) {
+            let (window, other_window) = (fade_window(keyframe), fade_window(other));
+            let overlaps = match (keyframe.anchor, keyframe.time, other.anchor, other.time) {
+                // Clock keyframes repeat every day
+                (None, Some(_), None, Some(_)) => [-DAY_IN_SECONDS, 0, DAY_IN_SECONDS]
+                    .iter()
+                    .any(|shift| overlap(window, (other_window.0 + shift, other_window.1 + shift))),
+                (Some(event), None, Some(other_event), None) => event == other_event && overlap(window, other_window),
+                _ => false,
+            };
 
-    Ok(default_config)
+            if overlaps {
+                overlapping.push((first, second));
+            }
+        }
+    }
+
+    overlapping
+}
+
+// Holidays first, then the first rule matching the date, if any
+pub fn profile_for(config: &Config, date: NaiveDate) -> Option<&str> {
+    if let Some(holidays) = config.holidays.as_ref().filter(|holidays| holidays.dates.contains(&date)) {
+        return Some(&holidays.profile);
+    }
+
+    config.profile_rules.iter()
+        .find(|rule| rule.matches(date))
+        .map(|rule| rule.profile.as_str())
+}
+
+// [screen] with the profile's settings on top of it. Tables are merged, so
+// a profile only needs the settings it changes
+fn profile_screen(config: &Config, name: &str) -> Result<ScreenConfig, String> {
+    let profile = config.profiles.get(name).ok_or_else(|| format!("Unknown profile \"{}\"", name))?;
+    let mut screen = toml::Table::try_from(&config.screen).map_err(|error| error.to_string())?;
+    merge_tables(&mut screen, &profile.screen);
+
+    screen.try_into().map_err(|error| format!("profiles.{}.screen: {}", name, error))
+}
+
+fn merge_tables(base: &mut toml::Table, overrides: &toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(override_table)) => merge_tables(base_table, override_table),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            },
+        }
+    }
+}
+
+// The config with the screen settings of the profile in effect on the given
+// local date
+pub fn config_for_date(config: &Config, date: NaiveDate) -> Result<Config, String> {
+    let Some(name) = profile_for(config, date) else {
+        return Ok(config.clone());
+    };
+
+    Ok(Config { screen: profile_screen(config, name)?, ..config.clone() })
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+trait FromNumber: Sized {
+    const DESCRIPTION: &'static str;
+
+    fn from_integer(value: i64) -> Option<Self>;
+    fn from_float(value: f64) -> Option<Self>;
+}
+
+impl FromNumber for u32 {
+    const DESCRIPTION: &'static str = "whole number";
+
+    fn from_integer(value: i64) -> Option<Self> {
+        u32::try_from(value).ok()
+    }
+
+    // 6000.0 is as whole as 6000
+    fn from_float(value: f64) -> Option<Self> {
+        if value.fract() != 0.0 || !(0.0..=u32::MAX as f64).contains(&value) {
+            return None;
+        }
+
+        Some(value as u32)
+    }
+}
+
+impl FromNumber for f64 {
+    const DESCRIPTION: &'static str = "number";
+
+    fn from_integer(value: i64) -> Option<Self> {
+        Some(value as f64)
+    }
+
+    fn from_float(value: f64) -> Option<Self> {
+        Some(value)
+    }
+}
+
+fn number_or_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromNumber + FromStr,
+{
+    let value = NumberOrString::deserialize(deserializer)
+        .map_err(|_| serde::de::Error::custom("expected a number"))?;
+
+    let number = match &value {
+        NumberOrString::Integer(integer) => T::from_integer(*integer),
+        NumberOrString::Float(float) => T::from_float(*float),
+        NumberOrString::String(string) => string.trim().parse::<T>().ok(),
+    };
+
+    number.ok_or_else(|| {
+        let shown = match value {
+            NumberOrString::Integer(integer) => integer.to_string(),
+            NumberOrString::Float(float) => format!("{:?}", float),
+            NumberOrString::String(string) => format!("\"{}\"", string),
+        };
+        serde::de::Error::custom(format!("{} is not a valid {}", shown, T::DESCRIPTION))
+    })
+}
+
+fn latitude<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    coordinate(deserializer, ('N', 'S'))
+}
+
+fn longitude<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    coordinate(deserializer, ('E', 'W'))
+}
+
+fn coordinate<'de, D: Deserializer<'de>>(deserializer: D, hemispheres: (char, char)) -> Result<f64, D::Error> {
+    match NumberOrString::deserialize(deserializer) {
+        Ok(NumberOrString::Integer(integer)) => Ok(integer as f64),
+        Ok(NumberOrString::Float(float)) => Ok(float),
+        Ok(NumberOrString::String(string)) => parse_coordinate(&string, hemispheres).map_err(serde::de::Error::custom),
+        Err(_) => Err(serde::de::Error::custom("expected a number or a string such as \"52°33'N\"")),
+    }
+}
+
+// Parses decimal degrees ("-13.39") or degrees, minutes and seconds with an
+// optional hemisphere letter at either end ("52°33'12\"N", "W 13.39")
+pub fn parse_coordinate(value: &str, hemispheres: (char, char)) -> Result<f64, String> {
+    let invalid = |reason: &str| format!("\"{}\" is not a valid coordinate: {}", value, reason);
+    let (positive, negative) = hemispheres;
+
+    let mut numeric = value.trim();
+    let mut hemisphere = None;
+    for letter in ['N', 'S', 'E', 'W'] {
+        for candidate in [letter, letter.to_ascii_lowercase()] {
+            if let Some(rest) = numeric.strip_suffix(candidate).or_else(|| numeric.strip_prefix(candidate)) {
+                if letter != positive && letter != negative {
+                    return Err(invalid(&format!("expected {} or {}, got {}", positive, negative, letter)));
+                }
+                if hemisphere.is_some() {
+                    return Err(invalid("more than one hemisphere letter"));
+                }
+                hemisphere = Some(letter);
+                numeric = rest.trim();
+            }
+        }
+    }
+
+    let parts = numeric
+        .split(|c: char| c.is_whitespace() || matches!(c, '°' | '\'' | '"' | '′' | '″'))
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<f64>().map_err(|_| invalid(&format!("\"{}\" is not a number", part))))
+        .collect::<Result<Vec<f64>, String>>()?;
+
+    let (degrees, minutes, seconds) = match parts.as_slice() {
+        [degrees] => (*degrees, 0.0, 0.0),
+        [degrees, minutes] => (*degrees, *minutes, 0.0),
+        [degrees, minutes, seconds] => (*degrees, *minutes, *seconds),
+        _ => return Err(invalid("expected degrees, minutes and seconds at most")),
+    };
+
+    if parts.len() > 1 && degrees.fract() != 0.0 {
+        return Err(invalid("degrees must be whole when minutes are given"));
+    }
+    if !(0.0..60.0).contains(&minutes) || !(0.0..60.0).contains(&seconds) {
+        return Err(invalid("minutes and seconds must be between 0 and 60"));
+    }
+    if hemisphere.is_some() && degrees.is_sign_negative() {
+        return Err(invalid("use either a minus sign or a hemisphere letter, not both"));
+    }
+
+    let magnitude = degrees.abs() + minutes / 60.0 + seconds / 3600.0;
+    let is_negative = degrees.is_sign_negative() || hemisphere == Some(negative);
+
+    Ok(if is_negative { -magnitude } else { magnitude })
 }
 
 #[cfg(test)]
 pub fn get_test_config() -> Config {
     Config {
         location: LocationConfig {
-            latitude: "52.56".to_string(),
-            longitude: "13.39".to_string(),
+            latitude: 52.56,
+            longitude: 13.39,
         },
         screen: ScreenConfig {
-            day_temperature: "6000".to_string(),
-            day_gamma: "100".to_string(),
-            night_temperature: "2800".to_string(),
-            night_gamma: "80".to_string(),
+            day_temperature: 6000,
+            day_gamma: 100.0,
+            night_temperature: 2800,
+            night_gamma: 80.0,
             fade_duration_in_minutes: 60,
+            interpolation: Interpolation::Kelvin,
+            morning: default_morning(),
+            evening: default_evening(),
+            keyframes: Vec::new(),
         },
         cache: CacheConfig {
             enabled: false,
-        }
+            prefetch_days: 0,
+            max_stale_days: 0,
+        },
+        sun_times: SunTimesConfig::default(),
+        schedule: ScheduleConfig::default(),
+        output: OutputConfig::default(),
+        profiles: BTreeMap::new(),
+        profile_rules: Vec::new(),
+        holidays: None,
     }
 }
 
@@ -110,18 +820,20 @@ mod tests {
         let _ = std::fs::remove_dir_all(&temp_dir);
         std::fs::create_dir_all(&temp_dir).unwrap();
 
-        let result = load_config(temp_dir.clone());
+        let result = load_config(&temp_dir.join(CONFIG_FILE_NAME), true);
         assert!(result.is_ok());
 
         let config = result.unwrap();
 
         // Default values
-        assert_eq!(config.location.latitude, "52.56");
-        assert_eq!(config.location.longitude, "13.39");
-        assert_eq!(config.screen.day_temperature, "6000");
-        assert_eq!(config.screen.day_gamma, "100");
-        assert_eq!(config.screen.night_temperature, "2800");
-        assert_eq!(config.screen.night_gamma, "80");
+        assert_eq!(config.location.latitude, 52.56);
+        assert_eq!(config.location.longitude, 13.39);
+        assert_eq!(config.screen.day_temperature, 6000);
+        assert_eq!(config.screen.day_gamma, 100.0);
+        assert_eq!(config.screen.night_temperature, 2800);
+        assert_eq!(config.screen.night_gamma, 80.0);
+        assert_eq!(config.sun_times.providers, vec![ProviderKind::SunriseSunsetIo, ProviderKind::Calculated]);
+        assert_eq!(config.sun_times.polar, PolarBehavior::FollowSun);
 
         let config_file = temp_dir.join("config.toml");
         assert!(config_file.exists());
@@ -129,6 +841,17 @@ mod tests {
         std::fs::remove_dir_all(&temp_dir).unwrap();
     }
 
+    #[test]
+    fn test_load_config_requires_explicit_file() {
+        let temp_dir = std::env::temp_dir().join("sundial_test_load_config_requires_explicit_file");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+
+        let config_file = temp_dir.join("sundail.toml");
+        let error = load_config(&config_file, false).unwrap_err().to_string();
+        assert_eq!(error, format!("{} does not exist, create it with `sundial config init`", config_file.display()));
+        assert!(!temp_dir.exists());
+    }
+
     #[test]
     fn test_load_config_reads_existing() {
         let temp_dir = std::env::temp_dir().join("sundial_test_load_config_reads_existing");
@@ -153,20 +876,475 @@ enabled = true
 "#;
         std::fs::write(&config_file, custom_config_content).unwrap();
 
-        let result = load_config(temp_dir.clone());
+        let result = load_config(&config_file, false);
         assert!(result.is_ok());
 
         let config = result.unwrap();
 
-        // Default values
-        assert_eq!(config.location.latitude, "40.71");
-        assert_eq!(config.location.longitude, "-74.12");
-        assert_eq!(config.screen.day_temperature, "5500");
-        assert_eq!(config.screen.day_gamma, "90");
-        assert_eq!(config.screen.night_temperature, "3000");
-        assert_eq!(config.screen.night_gamma, "70");
+        // Values from the file
+        assert_eq!(config.location.latitude, 40.71);
+        assert_eq!(config.location.longitude, -74.12);
+        assert_eq!(config.screen.day_temperature, 5500);
+        assert_eq!(config.screen.day_gamma, 90.0);
+        assert_eq!(config.screen.night_temperature, 3000);
+        assert_eq!(config.screen.night_gamma, 70.0);
         assert_eq!(config.screen.fade_duration_in_minutes, 120);
 
         std::fs::remove_dir_all(&temp_dir).unwrap();
     }
+
+    #[test]
+    fn test_load_config_fills_partial_sections() {
+        let temp_dir = std::env::temp_dir().join("sundial_test_load_config_fills_partial_sections");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let config_file = temp_dir.join("config.toml");
+        let partial_config_content = r#"
+[location]
+latitude = 52.56
+longitude = 13.39
+
+[screen]
+day_temperature = 6000
+day_gamma = 100
+night_temperature = 2800
+night_gamma = 80
+fade_duration_in_minutes = 60
+
+[cache]
+enabled = true
+
+[sun_times]
+providers = ["calculated"]
+
+[schedule]
+mode = "clock"
+"#;
+        std::fs::write(&config_file, partial_config_content).unwrap();
+
+        let config = load_config(&config_file, false).unwrap();
+        assert_eq!(config.sun_times.providers, vec![ProviderKind::Calculated]);
+        assert_eq!(config.sun_times.fixed_sunrise, NaiveTime::from_hms_opt(7, 0, 0).unwrap());
+        assert_eq!(config.sun_times.fixed_sunset, NaiveTime::from_hms_opt(19, 0, 0).unwrap());
+        assert_eq!(config.sun_times.polar, PolarBehavior::FollowSun);
+        assert_eq!(config.schedule.mode, ScheduleMode::Clock);
+        assert_eq!(config.schedule.day_start, NaiveTime::from_hms_opt(7, 0, 0).unwrap());
+        assert_eq!(config.schedule.night_start, NaiveTime::from_hms_opt(19, 0, 0).unwrap());
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    // The sections every config has, for the tests below to add to
+    const MINIMAL_TOML: &str = r#"
+[location]
+latitude = 52.56
+longitude = 13.39
+
+[screen]
+day_temperature = 6000
+day_gamma = 100
+night_temperature = 2800
+night_gamma = 80
+fade_duration_in_minutes = 60
+
+[cache]
+enabled = true
+"#;
+
+    fn config_from(toml: &str) -> Config {
+        let config: Config = toml::from_str(toml).unwrap();
+        validate_config(&config).unwrap();
+
+        config
+    }
+
+    #[test]
+    fn test_screen_numbers() {
+        let config = config_from(&MINIMAL_TOML
+            .replace("day_gamma = 100", "day_gamma = 90.0")
+            .replace("night_temperature = 2800", "night_temperature = \"3000\"")
+            .replace("night_gamma = 80", "night_gamma = \"70.5\""));
+
+        assert_eq!(config.screen.day_temperature, 6000);
+        assert_eq!(config.screen.day_gamma, 90.0);
+        assert_eq!(config.screen.night_temperature, 3000);
+        assert_eq!(config.screen.night_gamma, 70.5);
+    }
+
+    #[test]
+    fn test_coordinates() {
+        let config = config_from(&MINIMAL_TOML
+            .replace("latitude = 52.56", "latitude = \"40°42'36\\\"N\"")
+            .replace("longitude = 13.39", "longitude = -74.12"));
+
+        assert!((config.location.latitude - 40.71).abs() < 1e-9);
+        assert_eq!(config.location.longitude, -74.12);
+    }
+
+    #[test]
+    fn test_sun_times_config() {
+        let config = config_from(&format!("{}{}", MINIMAL_TOML, r#"
+[sun_times]
+providers = ["sunrise-sunset.org", "fixed"]
+fixed_sunrise = "06:30"
+fixed_sunset = "20:15"
+polar = "fixed"
+"#));
+
+        assert_eq!(config.sun_times.providers, vec![ProviderKind::SunriseSunsetOrg, ProviderKind::Fixed]);
+        assert_eq!(config.sun_times.fixed_sunrise, NaiveTime::from_hms_opt(6, 30, 0).unwrap());
+        assert_eq!(config.sun_times.fixed_sunset, NaiveTime::from_hms_opt(20, 15, 0).unwrap());
+        assert_eq!(config.sun_times.polar, PolarBehavior::Fixed);
+    }
+
+    #[test]
+    fn test_transitions() {
+        let config = config_from(&format!("{}{}", MINIMAL_TOML, r#"
+[screen.morning]
+anchor = "sunrise"
+align = "center"
+duration_in_minutes = 45
+
+[screen.evening]
+anchor = "golden_hour"
+align = "start"
+offset_in_minutes = -30
+curve = "smoothstep"
+"#));
+
+        assert_eq!(config.screen.morning.anchor, SolarEvent::Sunrise);
+        assert_eq!(config.screen.morning.align, Alignment::Center);
+        assert_eq!(config.screen.morning.duration_in_minutes, Some(45));
+        assert_eq!(config.screen.morning.offset_in_minutes, 0);
+        assert_eq!(config.screen.morning.curve, Curve::Linear);
+        assert_eq!(config.screen.evening.anchor, SolarEvent::GoldenHour);
+        assert_eq!(config.screen.evening.align, Alignment::Start);
+        assert_eq!(config.screen.evening.duration_in_minutes, None);
+        assert_eq!(config.screen.evening.offset_in_minutes, -30);
+        assert_eq!(config.screen.evening.curve, Curve::Smoothstep);
+    }
+
+    #[test]
+    fn test_interpolation() {
+        assert_eq!(config_from(MINIMAL_TOML).screen.interpolation, Interpolation::Kelvin);
+
+        let config = config_from(&MINIMAL_TOML.replace("fade_duration_in_minutes = 60", "fade_duration_in_minutes = 60\ninterpolation = \"mired\""));
+        assert_eq!(config.screen.interpolation, Interpolation::Mired);
+    }
+
+    #[test]
+    fn test_keyframes() {
+        let config = config_from(&format!("{}{}", MINIMAL_TOML, r#"
+[[screen.keyframes]]
+anchor = "sunrise"
+temperature = 6500
+gamma = 100
+
+[[screen.keyframes]]
+anchor = "sunset"
+offset_in_minutes = 30
+temperature = 3400
+gamma = 90
+curve = "sigmoid"
+
+[[screen.keyframes]]
+time = "23:00"
+temperature = 1900
+gamma = "60"
+duration_in_minutes = 90
+"#));
+
+        assert_eq!(config.screen.keyframes.len(), 3);
+        assert_eq!(config.screen.keyframes[0].anchor, Some(SolarEvent::Sunrise));
+        assert_eq!(config.screen.keyframes[0].align, Alignment::End);
+        assert_eq!(config.screen.keyframes[1].offset_in_minutes, 30);
+        assert_eq!(config.screen.keyframes[1].curve, Curve::Sigmoid);
+        assert_eq!(config.screen.keyframes[2].time, Some(NaiveTime::from_hms_opt(23, 0, 0).unwrap()));
+        assert_eq!(config.screen.keyframes[2].temperature, 1900);
+        assert_eq!(config.screen.keyframes[2].gamma, 60.0);
+        assert_eq!(config.screen.keyframes[2].duration_in_minutes, Some(90));
+    }
+
+    #[test]
+    fn test_schedule_config() {
+        let config = config_from(&format!("{}{}", MINIMAL_TOML, r#"
+[schedule]
+mode = "clock"
+day_start = "08:30"
+night_start = "22:00"
+"#));
+
+        assert_eq!(config.schedule.mode, ScheduleMode::Clock);
+        assert_eq!(config.schedule.day_start, NaiveTime::from_hms_opt(8, 30, 0).unwrap());
+        assert_eq!(config.schedule.night_start, NaiveTime::from_hms_opt(22, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_output_config() {
+        let config = config_from(MINIMAL_TOML);
+        assert_eq!(config.output.backend, BackendKind::Auto);
+        assert_eq!(config.output.startup_timeout_in_seconds, 5);
+
+        let config = config_from(&format!("{}{}", MINIMAL_TOML, r#"
+[output]
+backend = "hyprsunset_ipc"
+startup_timeout_in_seconds = 10
+"#));
+        assert_eq!(config.output.backend, BackendKind::HyprsunsetIpc);
+        assert_eq!(config.output.startup_timeout_in_seconds, 10);
+    }
+
+    // Loads the default config with default_line swapped for invalid_line,
+    // which has to fail with expected_error
+    fn assert_rejected(test_name: &str, test_cases: Vec<(&str, &str, &str)>) {
+        let temp_dir = std::env::temp_dir().join(test_name);
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let config_file = temp_dir.join("config.toml");
+        let default_toml = toml::to_string(&default_config()).unwrap();
+
+        for (default_line, invalid_line, expected_error) in test_cases {
+            assert!(default_toml.contains(default_line), "Default config lacks {}", default_line);
+            std::fs::write(&config_file, default_toml.replace(default_line, invalid_line)).unwrap();
+
+            let error = load_config(&config_file, false).unwrap_err().to_string();
+            assert!(error.contains(expected_error), "Unexpected error for {}: {}", invalid_line, error);
+        }
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_config_rejects_invalid_screen() {
+        assert_rejected("sundial_test_load_config_rejects_invalid_screen", vec![
+            ("day_temperature = 6000", "day_temperature = \"6OOO\"", "\"6OOO\" is not a valid whole number"),
+            ("day_temperature = 6000", "day_temperature = 60.5", "60.5 is not a valid whole number"),
+            ("day_temperature = 6000", "day_temperature = -6000.0", "-6000.0 is not a valid whole number"),
+            ("day_temperature = 6000", "day_temperature = 6e10", "60000000000.0 is not a valid whole number"),
+            ("day_temperature = 6000", "day_temperature = 60000.0", "screen.day_temperature must be between 1000 and 20000 K, got 60000"),
+            ("night_gamma = 80.0", "night_gamma = true", "expected a number"),
+            ("day_temperature = 6000", "day_temperature = 50000", "screen.day_temperature must be between 1000 and 20000 K, got 50000"),
+            ("night_gamma = 80.0", "night_gamma = -3", "screen.night_gamma must be between 0 and 200 %, got -3"),
+            ("fade_duration_in_minutes = 60", "fade_duration_in_minutes = -1", "screen.fade_duration_in_minutes must be between 0 and 720, got -1"),
+            ("offset_in_minutes = 0", "offset_in_minutes = 800", "screen.morning.offset_in_minutes must be between -720 and 720, got 800"),
+        ]);
+
+        // Floats without a fractional part are whole numbers too
+        let default_toml = toml::to_string(&default_config()).unwrap();
+        let config: Config = toml::from_str(&default_toml.replace("day_temperature = 6000", "day_temperature = 6000.0")).unwrap();
+        assert_eq!(config.screen.day_temperature, 6000);
+    }
+
+    #[test]
+    fn test_load_config_rejects_invalid_keyframes() {
+        assert_rejected("sundial_test_load_config_rejects_invalid_keyframes", vec![
+            ("[cache]", "[[screen.keyframes]]\ntemperature = 1900\ngamma = 60\n\n[cache]", "screen.keyframes[0] needs either an anchor or a time"),
+            ("[cache]", "[[screen.keyframes]]\ntime = \"23:00\"\ntemperature = 900\ngamma = 60\n\n[cache]", "screen.keyframes[0].temperature must be between 1000 and 20000 K, got 900"),
+        ]);
+
+        // Clock mode has no solar events to anchor keyframes to
+        let mut config = default_config();
+        config.schedule.mode = ScheduleMode::Clock;
+        config.screen.keyframes = vec![KeyframeConfig {
+            anchor: Some(SolarEvent::Sunset),
+            time: None,
+            offset_in_minutes: 0,
+            temperature: 1900,
+            gamma: 60.0,
+            duration_in_minutes: None,
+            align: Alignment::End,
+            curve: Curve::Linear,
+        }];
+        assert_eq!(
+            validate_config(&config).unwrap_err(),
+            "screen.keyframes[0] needs a time instead of an anchor, schedule.mode is \"clock\"",
+        );
+    }
+
+    #[test]
+    fn test_overlapping_keyframes() {
+        let keyframe = |placement: &str, fade: &str| format!("[[screen.keyframes]]\n{}\n{}\ntemperature = 3000\ngamma = 90\n", placement, fade);
+        let test_cases = vec![
+            (
+                vec![keyframe("time = \"22:00\"", ""), keyframe("time = \"23:00\"", "duration_in_minutes = 90")],
+                vec![(0, 1)],
+                "Clock keyframes",
+            ),
+            (
+                vec![keyframe("time = \"22:00\"", ""), keyframe("time = \"23:00\"", "")],
+                vec![],
+                "Clock keyframes fading back to back",
+            ),
+            (
+                vec![keyframe("time = \"23:30\"", "align = \"start\""), keyframe("time = \"00:15\"", "duration_in_minutes = 30")],
+                vec![(0, 1)],
+                "Clock keyframes across midnight",
+            ),
+            (
+                vec![keyframe("anchor = \"sunset\"", "align = \"center\""), keyframe("anchor = \"sunset\"", "offset_in_minutes = 20\nalign = \"start\"")],
+                vec![(0, 1)],
+                "Keyframes anchored to the same event",
+            ),
+            (
+                vec![keyframe("anchor = \"sunset\"", "align = \"center\""), keyframe("anchor = \"sunset\"", "offset_in_minutes = 30\nalign = \"start\"")],
+                vec![],
+                "Keyframes anchored to the same event, back to back",
+            ),
+            (
+                vec![keyframe("anchor = \"sunset\"", ""), keyframe("anchor = \"golden_hour\"", ""), keyframe("time = \"19:00\"", "")],
+                vec![],
+                "Different events and clock times depend on the day",
+            ),
+        ];
+
+        for (keyframes, expected, description) in test_cases {
+            let config: Config = toml::from_str(&format!("{}{}", MINIMAL_TOML, keyframes.join("\n"))).unwrap();
+            assert_eq!(overlapping_keyframes(&config.screen), expected, "Overlap failed for {}", description);
+        }
+
+        let config: Config = toml::from_str(&format!("{}{}{}", MINIMAL_TOML, keyframe("time = \"22:00\"", ""), keyframe("time = \"22:30\"", ""))).unwrap();
+        assert_eq!(validate_config(&config).unwrap_err(), "screen.keyframes[0] and screen.keyframes[1] have overlapping fades");
+    }
+
+    #[test]
+    fn test_load_config_rejects_invalid_location() {
+        assert_rejected("sundial_test_load_config_rejects_invalid_location", vec![
+            ("latitude = 52.56", "latitude = 95.0", "location.latitude must be between -90 and 90, got 95"),
+            ("longitude = 13.39", "longitude = \"-181\"", "location.longitude must be between -180 and 180, got -181"),
+            ("latitude = 52.56", "latitude = \"13.39E\"", "expected N or S, got E"),
+        ]);
+    }
+
+    #[test]
+    fn test_load_config_rejects_invalid_cache() {
+        assert_rejected("sundial_test_load_config_rejects_invalid_cache", vec![
+            ("prefetch_days = 7", "prefetch_days = 60", "cache.prefetch_days must be between 0 and 31, got 60"),
+            ("max_stale_days = 3", "max_stale_days = 32", "cache.max_stale_days must be between 0 and 31, got 32"),
+        ]);
+    }
+
+    #[test]
+    fn test_load_config_rejects_invalid_output() {
+        assert_rejected("sundial_test_load_config_rejects_invalid_output", vec![
+            ("startup_timeout_in_seconds = 5", "startup_timeout_in_seconds = 0", "output.startup_timeout_in_seconds must be between 1 and 300, got 0"),
+        ]);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let profiles_toml = r#"
+[profiles.weekend.screen]
+day_temperature = 6500
+
+[profiles.weekend.screen.morning]
+offset_in_minutes = 90
+
+[profiles.school_night.screen]
+night_temperature = 2400
+
+[profiles.school_night.screen.evening]
+duration_in_minutes = 30
+
+[[profile_rules]]
+profile = "weekend"
+weekdays = ["sat", "Sunday"]
+
+[[profile_rules]]
+profile = "school_night"
+weekdays = ["sun", "mon", "tue", "wed", "thu"]
+from = "2025-09-01"
+to = "2026-06-30"
+
+[[profile_rules]]
+profile = "school_night"
+dates = ["2025-08-31"]
+
+[holidays]
+profile = "weekend"
+dates = ["2025-12-25"]
+"#;
+        let default_toml = toml::to_string(&default_config()).unwrap();
+        let config: Config = toml::from_str(&format!("{}{}", default_toml, profiles_toml)).unwrap();
+        let date = |month: u32, day: u32| NaiveDate::from_ymd_opt(2025, month, day).unwrap();
+
+        assert!(validate_config(&config).is_ok());
+
+        let test_cases = vec![
+            (date(8, 29), None, "Friday"),
+            (date(8, 30), Some("weekend"), "Saturday"),
+            (date(8, 31), Some("weekend"), "Sunday, the weekend rule comes first"),
+            (date(8, 25), None, "Monday, before the school year"),
+            (date(9, 1), Some("school_night"), "Monday, school year"),
+            (date(12, 25), Some("weekend"), "Christmas, a Thursday"),
+        ];
+        for (date, expected_profile, description) in test_cases {
+            assert_eq!(profile_for(&config, date), expected_profile, "Profile failed for {}", description);
+        }
+
+        // Only what the profile sets changes
+        let weekend = config_for_date(&config, date(8, 30)).unwrap();
+        assert_eq!(weekend.screen.day_temperature, 6500);
+        assert_eq!(weekend.screen.morning.offset_in_minutes, 90);
+        assert_eq!(weekend.screen.morning.anchor, SolarEvent::Sunrise);
+        assert_eq!(weekend.screen.night_temperature, config.screen.night_temperature);
+
+        let school_night = config_for_date(&config, date(9, 1)).unwrap();
+        assert_eq!(school_night.screen.night_temperature, 2400);
+        assert_eq!(school_night.screen.evening.duration_in_minutes, Some(30));
+        assert_eq!(school_night.screen.day_temperature, config.screen.day_temperature);
+
+        assert_eq!(config_for_date(&config, date(8, 29)).unwrap().screen.day_temperature, config.screen.day_temperature);
+
+        // Problems in profiles are caught when loading the config
+        let invalid_toml = profiles_toml
+            .replace("day_temperature = 6500", "day_temperature = 50000")
+            .replace("profile = \"school_night\"\ndates", "profile = \"school\"\ndates");
+        let config: Config = toml::from_str(&format!("{}{}", default_toml, invalid_toml)).unwrap();
+        assert_eq!(
+            validate_config(&config).unwrap_err(),
+            "profiles.weekend.screen.day_temperature must be between 1000 and 20000 K, got 50000\n\
+             profile_rules[2].profile refers to an unknown profile \"school\"",
+        );
+    }
+
+    #[test]
+    fn test_parse_coordinate() {
+        let latitude = ('N', 'S');
+        let longitude = ('E', 'W');
+        let test_cases = vec![
+            ("52.56", latitude, 52.56),
+            ("-33.87", latitude, -33.87),
+            ("52.56N", latitude, 52.56),
+            ("33.87 S", latitude, -33.87),
+            ("s33.87", latitude, -33.87),
+            ("52°33'N", latitude, 52.55),
+            ("52° 33' 36\" N", latitude, 52.56),
+            ("13.39E", longitude, 13.39),
+            ("W 74.12", longitude, -74.12),
+            ("74°7′12″W", longitude, -74.12),
+        ];
+
+        for (value, hemispheres, expected) in test_cases {
+            let coordinate = parse_coordinate(value, hemispheres).unwrap();
+            assert!((coordinate - expected).abs() < 1e-9, "Parsing {} gave {}", value, coordinate);
+        }
+
+        let invalid_cases = vec![
+            ("", latitude),
+            ("north", latitude),
+            ("52.56W", latitude),
+            ("13.39N", longitude),
+            ("-52.56S", latitude),
+            ("52°61'N", latitude),
+            ("52.5°30'N", latitude),
+            ("1 2 3 4", longitude),
+            ("N52S", latitude),
+        ];
+
+        for (value, hemispheres) in invalid_cases {
+            assert!(parse_coordinate(value, hemispheres).is_err(), "Parsing {} should fail", value);
+        }
+    }
 }
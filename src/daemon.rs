@@ -0,0 +1,67 @@
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, info};
+
+// Sleeping is done in short chunks: the monotonic clock std::thread::sleep
+// relies on stops while the machine is suspended, so a single long sleep
+// would oversleep by however long the laptop lid was closed
+const MAX_SLEEP_CHUNK_IN_SECONDS: i64 = 60;
+// Wall clock drift tolerated before assuming the machine was suspended
+const SUSPEND_TOLERANCE_IN_SECONDS: i64 = 5;
+
+#[derive(Debug, PartialEq)]
+pub enum Wakeup {
+    // Deadline reached
+    OnTime,
+    // The wall clock jumped ahead, most likely a resume from suspend
+    Resumed,
+    // Woken early by the interrupted check
+    Interrupted,
+}
+
+pub fn sleep_until(deadline: DateTime<Utc>) -> Wakeup {
+    sleep_until_or(deadline, || false)
+}
+
+// Like sleep_until, but gives up early once interrupted returns true, which
+// is checked before every chunk
+pub fn sleep_until_or(deadline: DateTime<Utc>, interrupted: impl Fn() -> bool) -> Wakeup {
+    debug!("Sleeping until {}", deadline);
+
+    loop {
+        let before = Utc::now();
+        if before >= deadline {
+            return Wakeup::OnTime;
+        }
+        if interrupted() {
+            return Wakeup::Interrupted;
+        }
+
+        let chunk = (deadline - before).min(Duration::seconds(MAX_SLEEP_CHUNK_IN_SECONDS));
+        std::thread::sleep(chunk.to_std().unwrap_or_default());
+
+        let slept = Utc::now() - before;
+        if slept > chunk + Duration::seconds(SUSPEND_TOLERANCE_IN_SECONDS) {
+            info!("Woke up {}s late, assuming resume from suspend", (slept - chunk).num_seconds());
+            return Wakeup::Resumed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sleep_until() {
+        let past = Utc::now() - Duration::seconds(10);
+        assert_eq!(sleep_until(past), Wakeup::OnTime);
+
+        let soon = Utc::now() + Duration::milliseconds(50);
+        assert_eq!(sleep_until(soon), Wakeup::OnTime);
+        assert!(Utc::now() >= soon);
+
+        let later = Utc::now() + Duration::seconds(10);
+        assert_eq!(sleep_until_or(later, || true), Wakeup::Interrupted);
+        assert!(Utc::now() < later);
+    }
+}
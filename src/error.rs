@@ -0,0 +1,92 @@
+use std::fmt;
+
+// What went wrong, by what it means for whoever runs sundial. Every kind
+// exits with its own code, so scripts and systemd units can tell them apart
+#[derive(Debug)]
+pub enum SundialError {
+    // The config file is missing, unreadable or invalid, or so is an argument
+    Config(Box<dyn std::error::Error>),
+    // No sun times to be had, not even from the cache
+    Network(Box<dyn std::error::Error>),
+    // Sundial's data dir (cache and overrides) can't be read or written
+    Cache(Box<dyn std::error::Error>),
+    // The screen could not be changed
+    Backend(Box<dyn std::error::Error>),
+}
+
+impl SundialError {
+    pub fn config(error: impl Into<Box<dyn std::error::Error>>) -> Self {
+        SundialError::Config(error.into())
+    }
+
+    pub fn network(error: impl Into<Box<dyn std::error::Error>>) -> Self {
+        SundialError::Network(error.into())
+    }
+
+    pub fn cache(error: impl Into<Box<dyn std::error::Error>>) -> Self {
+        SundialError::Cache(error.into())
+    }
+
+    pub fn backend(error: impl Into<Box<dyn std::error::Error>>) -> Self {
+        SundialError::Backend(error.into())
+    }
+
+    // Above 1 and 2, the generic failure and clap's usage errors. Panics exit
+    // with 101
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            SundialError::Config(_) => 3,
+            SundialError::Network(_) => 4,
+            SundialError::Cache(_) => 5,
+            SundialError::Backend(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for SundialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SundialError::Config(error) => write!(f, "Config error: {}", error),
+            SundialError::Network(error) => write!(f, "Could not get sun times: {}", error),
+            SundialError::Cache(error) => write!(f, "Could not use the data dir: {}", error),
+            SundialError::Backend(error) => write!(f, "Could not change the screen: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SundialError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SundialError::Config(error)
+            | SundialError::Network(error)
+            | SundialError::Cache(error)
+            | SundialError::Backend(error) => Some(error.as_ref()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sundial_error() {
+        let errors = [
+            SundialError::config("location.latitude must be between -90 and 90, got 95"),
+            SundialError::network("No sun_times provider succeeded"),
+            SundialError::cache(std::io::Error::from(std::io::ErrorKind::PermissionDenied)),
+            SundialError::backend("hyprctl hyprsunset gamma 80 answered: invalid"),
+        ];
+
+        let mut exit_codes: Vec<u8> = errors.iter().map(SundialError::exit_code).collect();
+        exit_codes.sort();
+        exit_codes.dedup();
+        assert_eq!(exit_codes.len(), errors.len());
+        assert!(exit_codes.iter().all(|&code| code > 2));
+
+        assert_eq!(errors[0].to_string(), "Config error: location.latitude must be between -90 and 90, got 95");
+        assert_eq!(errors[1].to_string(), "Could not get sun times: No sun_times provider succeeded");
+        assert_eq!(errors[2].to_string(), "Could not use the data dir: permission denied");
+        assert_eq!(errors[3].to_string(), "Could not change the screen: hyprctl hyprsunset gamma 80 answered: invalid");
+    }
+}
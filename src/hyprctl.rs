@@ -0,0 +1,122 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+use log::debug;
+
+// What hyprctl prints when hyprsunset accepted the command
+const OK_OUTPUT: &str = "ok";
+
+#[derive(Debug)]
+pub enum HyprctlError {
+    // hyprctl could not be started at all, most likely not installed
+    Spawn(std::io::Error),
+    // hyprctl exited with an error
+    Failed { command: String, status: ExitStatus, output: String },
+    // hyprctl exited fine, but printed something other than "ok", e.g.
+    // when hyprsunset isn't listening (yet)
+    Rejected { command: String, output: String },
+}
+
+impl HyprctlError {
+    // hyprsunset might still be starting up, anything but a missing hyprctl
+    // is worth another try
+    pub fn is_transient(&self) -> bool {
+        !matches!(self, HyprctlError::Spawn(_))
+    }
+}
+
+impl fmt::Display for HyprctlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HyprctlError::Spawn(error) => write!(f, "Could not run hyprctl: {}", error),
+            HyprctlError::Failed { command, status, output } => write!(f, "hyprctl {} failed ({}): {}", command, status, output),
+            HyprctlError::Rejected { command, output } => write!(f, "hyprctl {} answered: {}", command, output),
+        }
+    }
+}
+
+impl std::error::Error for HyprctlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HyprctlError::Spawn(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+pub struct Hyprctl {
+    pub program: PathBuf,
+}
+
+impl Default for Hyprctl {
+    fn default() -> Self {
+        Hyprctl { program: PathBuf::from("hyprctl") }
+    }
+}
+
+impl Hyprctl {
+    // Runs `hyprctl hyprsunset <command>`
+    pub fn hyprsunset(&self, command: &str) -> Result<(), HyprctlError> {
+        let output = Command::new(&self.program)
+            .arg("hyprsunset")
+            .args(command.split(' '))
+            .output()
+            .map_err(HyprctlError::Spawn)?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        debug!("hyprctl hyprsunset {}: {} {}", command, output.status, stdout);
+
+        if !output.status.success() {
+            let message = if stderr.is_empty() { stdout } else { stderr };
+            return Err(HyprctlError::Failed { command: command.to_string(), status: output.status, output: message });
+        }
+        if stdout != OK_OUTPUT {
+            return Err(HyprctlError::Rejected { command: command.to_string(), output: stdout });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    // A stand-in for hyprctl that logs its arguments, prints the given
+    // output and exits with the given code
+    fn fake_hyprctl(temp_dir: &std::path::Path, output: &str, exit_code: i32) -> Hyprctl {
+        let program = temp_dir.join(format!("hyprctl-{}", exit_code));
+        let script = format!("#!/bin/sh\necho \"$@\" >> {}\necho \"{}\"\nexit {}\n", temp_dir.join("calls").display(), output, exit_code);
+        std::fs::write(&program, script).unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        Hyprctl { program }
+    }
+
+    #[test]
+    fn test_hyprsunset() {
+        let temp_dir = std::env::temp_dir().join("sundial_test_hyprctl");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        assert!(fake_hyprctl(&temp_dir, "ok", 0).hyprsunset("temperature 4000").is_ok());
+
+        let result = fake_hyprctl(&temp_dir, "Couldn't connect to .hyprsunset.sock", 0).hyprsunset("gamma 80");
+        assert!(matches!(&result, Err(HyprctlError::Rejected { output, .. }) if output == "Couldn't connect to .hyprsunset.sock"));
+        assert!(result.unwrap_err().is_transient());
+
+        let result = fake_hyprctl(&temp_dir, "oops", 3).hyprsunset("gamma 80");
+        assert!(matches!(&result, Err(HyprctlError::Failed { status, .. }) if status.code() == Some(3)));
+
+        let result = Hyprctl { program: temp_dir.join("missing") }.hyprsunset("gamma 80");
+        assert!(matches!(&result, Err(HyprctlError::Spawn(_))));
+        assert!(!result.unwrap_err().is_transient());
+
+        let calls = std::fs::read_to_string(temp_dir.join("calls")).unwrap();
+        assert_eq!(calls, "hyprsunset temperature 4000\nhyprsunset gamma 80\nhyprsunset gamma 80\n");
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use log::debug;
+
+// hyprsunset listens here, this is what `hyprctl hyprsunset ...` talks to
+const HYPRSUNSET_SOCKET_NAME: &str = ".hyprsunset.sock";
+const IPC_TIMEOUT_IN_SECONDS: u64 = 5;
+const OK_RESPONSE: &str = "ok";
+const READINESS_POLL_INTERVAL_IN_MILLISECONDS: u64 = 100;
+
+#[derive(Debug)]
+pub enum IpcError {
+    // Not running inside a Hyprland session
+    MissingEnvironment(&'static str),
+    // hyprsunset is most likely not running
+    Connect { path: PathBuf, source: std::io::Error },
+    Io(std::io::Error),
+    // hyprsunset answered, but not with "ok"
+    Rejected { command: String, response: String },
+    // hyprsunset was started, but never began listening
+    NotReady { path: PathBuf, timeout: Duration },
+}
+
+impl IpcError {
+    // hyprsunset might still be starting up and not listening yet
+    pub fn is_transient(&self) -> bool {
+        matches!(self, IpcError::Connect { .. } | IpcError::Io(_))
+    }
+}
+
+impl fmt::Display for IpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpcError::MissingEnvironment(variable) => write!(f, "{} is not set, is Hyprland running?", variable),
+            IpcError::Connect { path, source } => write!(f, "Could not connect to {}: {}", path.display(), source),
+            IpcError::Io(error) => write!(f, "Could not talk to hyprsunset: {}", error),
+            IpcError::Rejected { command, response } => write!(f, "hyprsunset rejected \"{}\": {}", command, response),
+            IpcError::NotReady { path, timeout } => write!(f, "hyprsunset did not start listening on {} within {:?}", path.display(), timeout),
+        }
+    }
+}
+
+impl std::error::Error for IpcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IpcError::Connect { source, .. } => Some(source),
+            IpcError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for IpcError {
+    fn from(error: std::io::Error) -> Self {
+        IpcError::Io(error)
+    }
+}
+
+// $XDG_RUNTIME_DIR/hypr/$HYPRLAND_INSTANCE_SIGNATURE/.hyprsunset.sock
+pub fn hyprsunset_socket_path(runtime_dir: Option<OsString>, instance_signature: Option<OsString>) -> Result<PathBuf, IpcError> {
+    let runtime_dir = runtime_dir.ok_or(IpcError::MissingEnvironment("XDG_RUNTIME_DIR"))?;
+    let instance_signature = instance_signature.ok_or(IpcError::MissingEnvironment("HYPRLAND_INSTANCE_SIGNATURE"))?;
+
+    Ok(PathBuf::from(runtime_dir).join("hypr").join(instance_signature).join(HYPRSUNSET_SOCKET_NAME))
+}
+
+pub struct HyprsunsetIpc {
+    pub socket_path: PathBuf,
+}
+
+impl HyprsunsetIpc {
+    pub fn from_env() -> Result<Self, IpcError> {
+        let socket_path = hyprsunset_socket_path(
+            std::env::var_os("XDG_RUNTIME_DIR"),
+            std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE"),
+        )?;
+
+        Ok(HyprsunsetIpc { socket_path })
+    }
+
+    // Polls until hyprsunset accepts connections, e.g. right after starting it
+    pub fn wait_until_ready(&self, timeout: Duration) -> Result<(), IpcError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if UnixStream::connect(&self.socket_path).is_ok() {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(IpcError::NotReady { path: self.socket_path.clone(), timeout });
+            }
+
+            std::thread::sleep(Duration::from_millis(READINESS_POLL_INTERVAL_IN_MILLISECONDS));
+        }
+    }
+
+    // One connection per command, the same way hyprctl does it
+    pub fn send(&self, command: &str) -> Result<(), IpcError> {
+        let mut stream = UnixStream::connect(&self.socket_path)
+            .map_err(|source| IpcError::Connect { path: self.socket_path.clone(), source })?;
+        let timeout = Some(Duration::from_secs(IPC_TIMEOUT_IN_SECONDS));
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+
+        stream.write_all(command.as_bytes())?;
+        stream.shutdown(std::net::Shutdown::Write)?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        debug!("hyprsunset answered \"{}\" to \"{}\"", response.trim(), command);
+
+        if response.trim() != OK_RESPONSE {
+            return Err(IpcError::Rejected { command: command.to_string(), response: response.trim().to_string() });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    // Stands in for hyprsunset: answers every command with the next
+    // response, and hands back the commands it got
+    fn fake_hyprsunset(socket_path: &PathBuf, responses: Vec<&'static str>) -> thread::JoinHandle<Vec<String>> {
+        let _ = std::fs::remove_file(socket_path);
+        let listener = UnixListener::bind(socket_path).unwrap();
+
+        thread::spawn(move || {
+            responses.into_iter().map(|response| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut command = String::new();
+                stream.read_to_string(&mut command).unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+                command
+            }).collect()
+        })
+    }
+
+    #[test]
+    fn test_send() {
+        let temp_dir = std::env::temp_dir().join("sundial_test_ipc_send");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let socket_path = temp_dir.join(HYPRSUNSET_SOCKET_NAME);
+
+        let server = fake_hyprsunset(&socket_path, vec!["ok", "invalid command"]);
+        let ipc = HyprsunsetIpc { socket_path: socket_path.clone() };
+
+        assert!(ipc.send("temperature 4000").is_ok());
+        match ipc.send("gamma 80.5") {
+            Err(IpcError::Rejected { command, response }) => {
+                assert_eq!(command, "gamma 80.5");
+                assert_eq!(response, "invalid command");
+            },
+            result => panic!("Expected a rejection, got {:?}", result),
+        }
+        assert_eq!(server.join().unwrap(), vec!["temperature 4000", "gamma 80.5"]);
+
+        // Nobody listening anymore
+        std::fs::remove_file(&socket_path).unwrap();
+        assert!(matches!(ipc.send("temperature 4000"), Err(IpcError::Connect { .. })));
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_wait_until_ready() {
+        let temp_dir = std::env::temp_dir().join("sundial_test_ipc_wait_until_ready");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let socket_path = temp_dir.join(HYPRSUNSET_SOCKET_NAME);
+        let ipc = HyprsunsetIpc { socket_path: socket_path.clone() };
+
+        let result = ipc.wait_until_ready(Duration::from_millis(250));
+        assert!(matches!(result, Err(IpcError::NotReady { .. })));
+        assert!(!result.unwrap_err().is_transient());
+
+        // Starts listening a bit after we begin waiting
+        let starting = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            UnixListener::bind(&socket_path).unwrap()
+        });
+        assert!(ipc.wait_until_ready(Duration::from_secs(5)).is_ok());
+        starting.join().unwrap();
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_hyprsunset_socket_path() {
+        let path = hyprsunset_socket_path(Some("/run/user/1000".into()), Some("abc_123".into())).unwrap();
+        assert_eq!(path, PathBuf::from("/run/user/1000/hypr/abc_123/.hyprsunset.sock"));
+
+        assert!(matches!(
+            hyprsunset_socket_path(Some("/run/user/1000".into()), None),
+            Err(IpcError::MissingEnvironment("HYPRLAND_INSTANCE_SIGNATURE")),
+        ));
+        assert!(matches!(hyprsunset_socket_path(None, None), Err(IpcError::MissingEnvironment("XDG_RUNTIME_DIR"))));
+    }
+}
@@ -1,142 +1,542 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use clap::Parser;
 use notify_rust::{Notification, Timeout, Urgency};
 use log::{info, warn, error, debug };
 
+mod cli;
+use cli::{CacheCommand, Cli, Command, ConfigCommand};
+
+mod error;
+use error::SundialError;
+
 mod config;
-use config::{Config, get_config_dir, load_config};
+use config::{CONFIG_FILE_NAME, Config, ScheduleMode, config_for_date, get_config_dir, load_config, profile_for, validate_screen_state, write_default_config};
 
 mod sun_times;
-use sun_times::{SunTimes, build_sunrisesunset_url, fetch_sunrise_sunset};
+use sun_times::SunTimes;
+
+mod solar;
+
+mod providers;
+use providers::{SunTimesProvider, apply_polar_behavior, build_providers, fetch_sun_times};
 
 mod screen;
-use screen::{calculate_screen_state};
+use screen::{ScreenState, calculate_screen_state, override_is_over, time_until_next_change};
+
+mod hyprctl;
+
+mod ipc;
+
+mod retry;
+
+mod backends;
+use backends::{OutputBackend, build_backend};
+
+mod daemon;
+use daemon::{Wakeup, sleep_until, sleep_until_or};
+
+mod overrides;
+use overrides::{ScreenOverride, load_override, remove_override, save_override};
 
 mod cache;
-use cache::{get_data_dir, load_cache, persist_to_cache};
+use cache::{Cache, CacheEntry, clear_cache, get_data_dir, load_cache, persist_to_cache};
 
 struct Application {
     config: Config,
     data_dir: PathBuf,
+    providers: Vec<Box<dyn SunTimesProvider>>,
+    // Everything runs except for what the output backend would do to the
+    // screen, which is printed instead
+    dry_run: bool,
 }
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 impl Application {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        env_logger::init();
-
-        let config_dir = get_config_dir()?;
-        let data_dir = get_data_dir()?;
-        let config = load_config(config_dir.clone())?;
+    pub fn new(config_file: &Path, create_config: bool, dry_run: bool) -> Result<Self, SundialError> {
+        let data_dir = get_data_dir().map_err(SundialError::cache)?;
+        let config = load_config(config_file, create_config).map_err(SundialError::config)?;
+        let providers = build_providers(&config);
 
         info!("Starting out {}", NAME);
         info!("Version: {}", VERSION);
 
-        Ok(Application { config, data_dir })
+        Ok(Application { config, data_dir, providers, dry_run })
+    }
+
+    pub fn run(&self) -> Result<(), SundialError> {
+        let screen_override = self.prune_override(Utc::now())?;
+        self.run_with_override(screen_override.as_ref())
+    }
+
+    // Like run, but with that override in place of the saved one, so dry
+    // runs can show what an override does without saving it
+    fn run_with_override(&self, screen_override: Option<&ScreenOverride>) -> Result<(), SundialError> {
+        let backend = build_backend(&self.config, self.dry_run).map_err(SundialError::backend)?;
+        backend.prepare(self.dry_run.then_some(&mut std::io::stdout())).map_err(SundialError::backend)?;
+        self.manage_screen(backend.as_ref(), screen_override)?;
+
+        Ok(())
+    }
+
+    pub fn status(&self) -> Result<(), SundialError> {
+        let sun_times = self.get_sun_times()?;
+        let now = Utc::now();
+        let screen_override = self.get_override(now)?;
+        let screen_state = calculate_screen_state(now, sun_times.as_ref(), &self.config, screen_override.as_ref());
+
+        if let Some(profile) = profile_for(&self.config, now.with_timezone(&Local).date_naive()) {
+            println!("Profile: {}", profile);
+        }
+        match sun_times {
+            Some(SunTimes::Regular { sunrise, sunset, .. }) => {
+                println!("Sunrise: {} ({} UTC)", sunrise.with_timezone(&Local).format("%H:%M"), sunrise.format("%H:%M"));
+                println!("Sunset: {} ({} UTC)", sunset.with_timezone(&Local).format("%H:%M"), sunset.format("%H:%M"));
+            },
+            Some(SunTimes::PolarDay) => println!("Polar day: the sun does not set today"),
+            Some(SunTimes::PolarNight) => println!("Polar night: the sun does not rise today"),
+            None => {
+                let schedule = &self.config.schedule;
+                println!("Clock schedule: day at {}, night at {}", schedule.day_start.format("%H:%M"), schedule.night_start.format("%H:%M"));
+            },
+        }
+        match screen_override.map(|screen_override| screen_override.until) {
+            Some(Some(until)) if until > now => println!("Override until {}", until.with_timezone(&Local).format("%H:%M")),
+            Some(Some(until)) => println!("Fading back from an override since {}", until.with_timezone(&Local).format("%H:%M")),
+            Some(None) => println!("Override until `sundial resume`"),
+            None => {},
+        }
+        println!("Temperature: {}K", screen_state.temperature);
+        println!("Gamma: {}%", screen_state.gamma);
+
+        Ok(())
+    }
+
+    pub fn preview(&self, at: NaiveTime) -> Result<(), SundialError> {
+        let sun_times = self.get_sun_times()?;
+        let time = local_to_utc(at).map_err(SundialError::config)?;
+        let screen_override = self.get_override(Utc::now())?;
+        let screen_state = calculate_screen_state(time, sun_times.as_ref(), &self.config, screen_override.as_ref());
+
+        println!("At {}:", at.format("%H:%M"));
+        println!("Temperature: {}K", screen_state.temperature);
+        println!("Gamma: {}%", screen_state.gamma);
+
+        Ok(())
+    }
+
+    pub fn set_override(&self, temperature: Option<u32>, gamma: Option<f64>, duration: Option<chrono::Duration>) -> Result<(), SundialError> {
+        let now = Utc::now();
+        let config = self.config_at(now)?;
+        let screen_state = ScreenState {
+            temperature: temperature.unwrap_or(config.screen.day_temperature),
+            gamma: gamma.unwrap_or(config.screen.day_gamma),
+        };
+        validate_screen_state(screen_state.temperature, screen_state.gamma).map_err(SundialError::config)?;
+
+        let screen_override = self.hold(screen_state, now, duration)?;
+        self.run_with_override(Some(&screen_override))
     }
 
-    pub fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
-        self.start_hyprsunset()?;
-        self.manage_screen()?;
+    // Holds whatever the screen is at right now
+    pub fn pause(&self, duration: Option<chrono::Duration>) -> Result<(), SundialError> {
+        let sun_times = self.get_sun_times()?;
+        let now = Utc::now();
+        let screen_override = self.get_override(now)?;
+        let screen_state = calculate_screen_state(now, sun_times.as_ref(), &self.config, screen_override.as_ref());
+
+        self.hold(screen_state, now, duration)?;
 
         Ok(())
     }
 
-    fn start_hyprsunset(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let hyprsunset_process = std::process::Command::new("pgrep")
-            .arg("hyprsunset")
-            .output()?;
-        let is_hyprsunset_running = hyprsunset_process.status.success();
-        if is_hyprsunset_running {
-            debug!("Hyprsunset is running. Process: {:?}", hyprsunset_process);
+    // Lets the override expire right away, so the screen fades back
+    pub fn resume(&self) -> Result<(), SundialError> {
+        let now = Utc::now();
+        let Some(mut screen_override) = self.get_override(now)? else {
+            println!("No override to resume from");
             return Ok(());
+        };
+
+        let fade_duration = self.config_at(now)?.screen.fade_duration_in_minutes;
+        if self.dry_run {
+            println!("[dry-run] Would fade back into the schedule over {} minutes", fade_duration);
+        } else {
+            println!("Fading back into the schedule over {} minutes", fade_duration);
         }
 
-        let new_hyprsunset_process = std::process::Command::new("systemctl")
-            .args(["--user", "start", "hyprsunset"])
-            .output()?;
-        debug!("Starting hyprsunset. Process: {:?}", new_hyprsunset_process);
+        if screen_override.until.is_none_or(|until| until > now) {
+            screen_override.until = Some(now);
+            if !self.dry_run {
+                save_override(&self.data_dir, &screen_override).map_err(SundialError::cache)?;
+            }
+        }
+
+        self.run_with_override(Some(&screen_override))
+    }
+
+    fn hold(&self, screen_state: ScreenState, now: DateTime<Utc>, duration: Option<chrono::Duration>) -> Result<ScreenOverride, SundialError> {
+        let until = match duration {
+            Some(duration) => Some(now.checked_add_signed(duration).ok_or_else(|| {
+                SundialError::config(format!("Cannot hold for {} days, that is past any date sundial can handle", duration.num_days()))
+            })?),
+            None => None,
+        };
+        let screen_override = ScreenOverride { screen_state, from: now, until };
+        let until = match until {
+            Some(until) => until.with_timezone(&Local).format("%H:%M").to_string(),
+            None => "`sundial resume`".to_string(),
+        };
+
+        if self.dry_run {
+            println!("[dry-run] Would hold {}K and {}% until {}", screen_state.temperature, screen_state.gamma, until);
+        } else {
+            save_override(&self.data_dir, &screen_override).map_err(SundialError::cache)?;
+            println!("Holding {}K and {}% until {}", screen_state.temperature, screen_state.gamma, until);
+        }
+
+        Ok(screen_override)
+    }
+
+    pub fn show_cache(&self) -> Result<(), SundialError> {
+        let cache = load_cache(&self.config, &self.data_dir).map_err(SundialError::cache)?;
+        if cache.entries.is_empty() {
+            println!("No cached sun times");
+        } else {
+            println!("{}", serde_json::to_string_pretty(&cache).map_err(SundialError::cache)?);
+        }
+
+        Ok(())
+    }
+
+    pub fn clear_cache(&self) -> Result<(), SundialError> {
+        let removed = clear_cache(&self.data_dir).map_err(SundialError::cache)?;
+        println!("Removed {} cache file(s) from {}", removed, self.data_dir.display());
 
         Ok(())
     }
 
-    fn get_sun_times(&self) -> Result<SunTimes, Box<dyn std::error::Error>> {
-        Ok(
-            match load_cache(&self.config, &self.data_dir) {
-                Ok(Some(cache)) => {
-                    let cached_sun_times = cache.sun_times;
+    // Stays resident, applying a new screen state only when it changes:
+    // every minute while fading and not at all in between
+    pub fn run_daemon(&self) -> Result<(), SundialError> {
+        let retry_delay = chrono::Duration::minutes(1);
+        let mut sun_times_for_day: Option<(chrono::NaiveDate, Option<SunTimes>)> = None;
+        let mut applied_state: Option<ScreenState> = None;
+        let backend = build_backend(&self.config, self.dry_run).map_err(SundialError::backend)?;
+
+        info!("Running as a daemon");
+
+        loop {
+            let now = chrono::Utc::now();
+            let today = now.date_naive();
+
+            let sun_times = match &sun_times_for_day {
+                Some((day, sun_times)) if *day == today => sun_times.clone(),
+                _ => match self.get_sun_times() {
+                    Ok(sun_times) => {
+                        sun_times_for_day = Some((today, sun_times.clone()));
+                        sun_times
+                    },
+                    Err(error) => {
+                        error!("Could not get sun_times, retrying in a minute: {}", error);
+                        sleep_until(now + retry_delay);
+                        continue;
+                    }
+                }
+            };
+
+            let screen_override = self.prune_override(now).unwrap_or_else(|error| {
+                warn!("Ignoring the override: {}", error);
+                None
+            });
+            let screen_state = calculate_screen_state(now, sun_times.as_ref(), &self.config, screen_override.as_ref());
+            let mut wait = time_until_next_change(now, sun_times.as_ref(), &self.config, screen_override.as_ref());
+
+            if applied_state.as_ref() != Some(&screen_state) {
+                let applied = backend.prepare(self.dry_run.then_some(&mut std::io::stdout()))
+                    .map_err(SundialError::backend)
+                    .and_then(|_| self.apply_screen_state(backend.as_ref(), &screen_state));
+                match applied {
+                    Ok(()) => applied_state = Some(screen_state),
+                    Err(error) => {
+                        error!("Could not apply screen state, retrying in a minute: {}", error);
+                        wait = wait.min(retry_delay);
+                    }
+                }
+            }
+
+            // `sundial override`, `pause` and `resume` change the override
+            // file while we're asleep
+            let override_changed = || load_override(&self.data_dir).ok().flatten() != screen_override;
+            if sleep_until_or(now + wait, override_changed) == Wakeup::Resumed {
+                // The backend might have been restarted while we were asleep
+                applied_state = None;
+            }
+        }
+    }
+
+    // The override in the data dir, unless it no longer matters
+    fn get_override(&self, now: DateTime<Utc>) -> Result<Option<ScreenOverride>, SundialError> {
+        let screen_override = load_override(&self.data_dir).map_err(SundialError::cache)?;
+
+        Ok(screen_override.filter(|screen_override| !override_is_over(screen_override, now, &self.config)))
+    }
+
+    // Like get_override, but also removes the override once it no longer
+    // matters. Only run and daemon clean up, the other commands and dry runs
+    // just read
+    fn prune_override(&self, now: DateTime<Utc>) -> Result<Option<ScreenOverride>, SundialError> {
+        let Some(screen_override) = load_override(&self.data_dir).map_err(SundialError::cache)? else {
+            return Ok(None);
+        };
 
-                    debug!("Cached sun_times in UTC: {:?}", cached_sun_times);
+        if override_is_over(&screen_override, now, &self.config) {
+            if !self.dry_run {
+                debug!("Override is over, removing it");
+                remove_override(&self.data_dir).map_err(SundialError::cache)?;
+            }
+            return Ok(None);
+        }
+
+        Ok(Some(screen_override))
+    }
+
+    // Config with the screen settings of the profile in effect at that time
+    fn config_at(&self, time: DateTime<Utc>) -> Result<Config, SundialError> {
+        let date = time.with_timezone(&Local).date_naive();
+        if let Some(profile) = profile_for(&self.config, date) {
+            debug!("Using profile {} on {}", profile, date);
+        }
+
+        config_for_date(&self.config, date).map_err(SundialError::config)
+    }
 
-                    cached_sun_times
+    // Fetches today's sun times into the cache, along with the name of the
+    // provider they came from
+    fn fetch_sun_times(&self, cache: &mut Cache, today: NaiveDate) -> Result<(&'static str, SunTimes), Box<dyn std::error::Error>> {
+        let location = &self.config.location;
+        let (provider_name, sun_times) = fetch_sun_times(&self.providers, location, today)?;
+        cache.insert(self.cache_entry(provider_name, today, sun_times.clone()));
+
+        Ok((provider_name, sun_times))
+    }
+
+    // Fetches the days after today into the cache from the same provider,
+    // stopping at the first one it can't get
+    fn prefetch_sun_times(&self, cache: &mut Cache, provider_name: &str, today: NaiveDate) {
+        let location = &self.config.location;
+        let Some(provider) = self.providers.iter().find(|provider| provider.name() == provider_name) else {
+            return;
+        };
+
+        for date in today.iter_days().skip(1).take(self.config.cache.prefetch_days as usize) {
+            if cache.get(location, &[provider_name], date).is_some() {
+                continue;
+            }
+
+            match provider.sun_times(location, date) {
+                Ok(prefetched) => cache.insert(self.cache_entry(provider_name, date, prefetched)),
+                Err(error) => {
+                    warn!("Could not prefetch sun_times for {}: {}", date, error);
+                    break;
                 },
-                Ok(None) => {
-                    let url = build_sunrisesunset_url(&self.config);
-                    let sun_times = fetch_sunrise_sunset(&url)?;
-                    persist_to_cache(&self.config, &self.data_dir, &sun_times)?;
+            }
+        }
+    }
+
+    // Keeps the next prefetch_days days cached, so sundial keeps working
+    // offline for that long. Tops up at most once a day, and not at all
+    // without a cache to keep the days in
+    fn top_up_cache(&self, cache: &mut Cache, provider_name: &str, today: NaiveDate) -> Result<(), SundialError> {
+        let last_day = today + chrono::Days::new(self.config.cache.prefetch_days as u64);
+        let topped_up = cache.get(&self.config.location, &[provider_name], last_day).is_some();
+        if !self.config.cache.enabled || topped_up || cache.prefetched_on == Some(today) {
+            return Ok(());
+        }
+
+        self.prefetch_sun_times(cache, provider_name, today);
+        cache.prefetched_on = Some(today);
+        persist_to_cache(&self.config, &self.data_dir, cache, today).map_err(SundialError::cache)?;
+
+        Ok(())
+    }
+
+    fn cache_entry(&self, provider_name: &str, date: NaiveDate, sun_times: SunTimes) -> CacheEntry {
+        CacheEntry {
+            latitude: self.config.location.latitude,
+            longitude: self.config.location.longitude,
+            provider: provider_name.to_string(),
+            date,
+            sun_times,
+        }
+    }
+
+    // None in clock mode, where the sun doesn't matter
+    fn get_sun_times(&self) -> Result<Option<SunTimes>, SundialError> {
+        if self.config.schedule.mode == ScheduleMode::Clock {
+            debug!("Clock schedule, skipping sun_times");
+            return Ok(None);
+        }
+
+        let today = chrono::Utc::now().date_naive();
+        let mut cache = load_cache(&self.config, &self.data_dir).unwrap_or_else(|error| {
+            warn!("[Cache error] Starting over: {}", error);
+            Cache::default()
+        });
+        let provider_names: Vec<&str> = self.providers.iter().map(|provider| provider.name()).collect();
+
+        let sun_times = match cache.get(&self.config.location, &provider_names, today) {
+            Some(entry) => {
+                debug!("Cached sun_times from {} in UTC: {:?}", entry.provider, entry.sun_times);
+
+                let (provider_name, sun_times) = (entry.provider.clone(), entry.sun_times.clone());
+                self.top_up_cache(&mut cache, &provider_name, today)?;
+
+                sun_times
+            },
+            None => match self.fetch_sun_times(&mut cache, today) {
+                Ok((provider_name, sun_times)) => {
+                    persist_to_cache(&self.config, &self.data_dir, &mut cache, today).map_err(SundialError::cache)?;
 
                     debug!("[No cache] Fresh sun_times in UTC: {:?}", sun_times);
 
+                    self.top_up_cache(&mut cache, provider_name, today)?;
+
                     sun_times
                 },
-                Err(_) => {
-                    let url = build_sunrisesunset_url(&self.config);
-                    let sun_times = fetch_sunrise_sunset(&url)?;
-                    persist_to_cache(&self.config, &self.data_dir, &sun_times)?;
+                // Sun times only move by minutes a day, a few days old ones
+                // are better than none
+                Err(error) => {
+                    let max_stale_days = self.config.cache.max_stale_days;
+                    let Some(entry) = cache.closest(&self.config.location, &provider_names, today, max_stale_days) else {
+                        return Err(SundialError::network(error));
+                    };
 
-                    warn!("[Cache error] Fresh sun_times in UTC: {:?}", sun_times);
+                    warn!("Could not fetch sun_times ({}), falling back to the ones cached for {}", error, entry.date);
 
-                    sun_times
-                }
-            }
-        )
+                    entry.sun_times.shifted(today - entry.date)
+                },
+            },
+        };
+
+        // Only fails for fixed times that don't exist locally that day
+        let sun_times = apply_polar_behavior(sun_times, &self.config, today).map_err(SundialError::config)?;
+
+        Ok(Some(sun_times))
     }
 
-    fn manage_screen(&self) -> Result<(), Box<dyn std::error::Error>> {
+    fn manage_screen(&self, backend: &dyn OutputBackend, screen_override: Option<&ScreenOverride>) -> Result<(), SundialError> {
         let sun_times = self.get_sun_times()?;
-        let now = chrono::Utc::now().time();
-        let screen_state = calculate_screen_state(now, &sun_times, &self.config);
+        let now = chrono::Utc::now();
+        let screen_state = calculate_screen_state(now, sun_times.as_ref(), &self.config, screen_override);
+
+        self.apply_screen_state(backend, &screen_state)
+    }
 
+    fn apply_screen_state(&self, backend: &dyn OutputBackend, screen_state: &ScreenState) -> Result<(), SundialError> {
         let info_log = format!("Setting screen to: {:?}", screen_state);
         info!("{}", &info_log);
 
         if log::log_enabled!(log::Level::Trace) {
-            // Swallow error since it doesn't really matter
-            // if it errors out here
-            let _ = Notification::new()
-                    .summary("Sundial")
-                    .body(&info_log)
-                    .timeout(Timeout::Milliseconds(6000))
-                    .urgency(Urgency::Low)
-                    .show()?;
-        }
-
-        std::process::Command::new("hyprctl")
-            .args(["hyprsunset", "temperature", &screen_state.temperature])
-            .output()?;
-        std::process::Command::new("hyprctl")
-            .args(["hyprsunset", "gamma", &screen_state.gamma])
-            .output()?;
+            notify(&info_log, Urgency::Low);
+        }
 
-        Ok(())
+        backend.apply(screen_state, self.dry_run.then_some(&mut std::io::stdout())).map_err(SundialError::backend)
+    }
+}
+
+fn local_to_utc(time: NaiveTime) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+    let datetime = Local.from_local_datetime(&Local::now().date_naive().and_time(time))
+        .earliest()
+        .ok_or_else(|| format!("{} does not exist in local time today", time))?;
+
+    Ok(datetime.with_timezone(&Utc))
+}
+
+fn run_config_command(action: ConfigCommand, config_file: &Path, create_config: bool) -> Result<(), SundialError> {
+    match action {
+        ConfigCommand::Init { force } => {
+            if config_file.exists() && !force {
+                return Err(SundialError::config(format!("{} already exists, use --force to overwrite it", config_file.display())));
+            }
+
+            write_default_config(config_file).map_err(SundialError::config)?;
+            println!("Wrote default config to {}", config_file.display());
+        },
+        ConfigCommand::Show => {
+            let config = load_config(config_file, create_config).map_err(SundialError::config)?;
+            println!("# {}", config_file.display());
+            print!("{}", toml::to_string(&config).map_err(SundialError::config)?);
+        },
+        ConfigCommand::Validate => {
+            load_config(config_file, false).map_err(SundialError::config)?;
+            println!("{} is valid", config_file.display());
+        },
+    }
+
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<(), SundialError> {
+    // Only the default config file gets created on first use
+    let create_config = cli.config.is_none();
+    let config_file = match cli.config {
+        Some(config_file) => config_file,
+        None => get_config_dir().map_err(SundialError::config)?.join(CONFIG_FILE_NAME),
+    };
+
+    let command = cli.command.unwrap_or(Command::Run);
+    if let Command::Config { action } = command {
+        return run_config_command(action, &config_file, create_config);
+    }
+
+    let application = Application::new(&config_file, create_config, cli.dry_run)?;
+    match command {
+        Command::Run => application.run(),
+        Command::Daemon => application.run_daemon(),
+        Command::Status => application.status(),
+        Command::Preview { at } => application.preview(at),
+        Command::Override { temperature, gamma, duration } => application.set_override(temperature, gamma, duration),
+        Command::Pause { duration } => application.pause(duration),
+        Command::Resume => application.resume(),
+        Command::Cache { action: CacheCommand::Show } => application.show_cache(),
+        Command::Cache { action: CacheCommand::Clear } => application.clear_cache(),
+        Command::Config { .. } => unreachable!(),
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    match Application::new()?.run() {
-        Ok(()) => { Ok(()) },
+// Best effort: headless sessions have no notification daemon to talk to
+fn notify(body: &str, urgency: Urgency) {
+    let shown = Notification::new()
+        .summary("Sundial")
+        .body(body)
+        .timeout(Timeout::Milliseconds(6000))
+        .urgency(urgency)
+        .show();
+
+    if let Err(error) = shown {
+        debug!("Could not show a notification: {}", error);
+    }
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+
+    let cli = Cli::parse();
+    let notify_on_error = !cli.no_notify;
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
         Err(error) => {
-            let err = format!("Error: {:?}", error);
-            error!("{}", &err);
-            Notification::new()
-                .summary("Sundial")
-                .body(&err)
-                .timeout(Timeout::Milliseconds(6000))
-                .urgency(Urgency::Critical)
-                .show()
-                .unwrap();
-            panic!("ERROR!ERROR!ERROR!");
+            error!("{}", error);
+            if notify_on_error {
+                notify(&error.to_string(), Urgency::Critical);
+            }
+
+            ExitCode::from(error.exit_code())
         }
     }
 }
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::screen::ScreenState;
+
+const OVERRIDE_FILE_NAME: &str = "override.json";
+
+// A screen state held regardless of the schedule, set by `sundial override`
+// or `sundial pause`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ScreenOverride {
+    pub screen_state: ScreenState,
+    pub from: DateTime<Utc>,
+    // None holds it until `sundial resume`
+    pub until: Option<DateTime<Utc>>,
+}
+
+fn override_file(data_dir: &Path) -> PathBuf {
+    data_dir.join(OVERRIDE_FILE_NAME)
+}
+
+pub fn load_override(data_dir: &Path) -> Result<Option<ScreenOverride>, Box<dyn std::error::Error>> {
+    let override_file = override_file(data_dir);
+
+    if !override_file.exists() {
+        return Ok(None);
+    }
+
+    let override_content = std::fs::read_to_string(override_file)?;
+    let screen_override: ScreenOverride = serde_json::from_str(&override_content)?;
+
+    Ok(Some(screen_override))
+}
+
+// Written next to the override file first and renamed over it, so the
+// daemon polling it never reads it half written
+pub fn save_override(data_dir: &Path, screen_override: &ScreenOverride) -> Result<(), Box<dyn std::error::Error>> {
+    let override_file = override_file(data_dir);
+    let temporary_file = override_file.with_extension("json.tmp");
+    let override_content = serde_json::to_string(screen_override)?;
+
+    std::fs::write(&temporary_file, override_content)?;
+    std::fs::rename(&temporary_file, &override_file)?;
+
+    Ok(())
+}
+
+pub fn remove_override(data_dir: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let override_file = override_file(data_dir);
+
+    if !override_file.exists() {
+        return Ok(false);
+    }
+
+    std::fs::remove_file(override_file)?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_override_save_load_remove() {
+        let temp_dir = std::env::temp_dir().join("sundial_test_override_save_load_remove");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        assert_eq!(load_override(&temp_dir).unwrap(), None);
+        assert!(!remove_override(&temp_dir).unwrap());
+
+        let screen_override = ScreenOverride {
+            screen_state: ScreenState { temperature: 6500, gamma: 100.0 },
+            from: Utc.with_ymd_and_hms(2025, 8, 17, 20, 0, 0).unwrap(),
+            until: Some(Utc.with_ymd_and_hms(2025, 8, 17, 22, 0, 0).unwrap()),
+        };
+        save_override(&temp_dir, &screen_override).unwrap();
+        assert!(!temp_dir.join("override.json.tmp").exists());
+        assert_eq!(load_override(&temp_dir).unwrap(), Some(screen_override));
+
+        assert!(remove_override(&temp_dir).unwrap());
+        assert_eq!(load_override(&temp_dir).unwrap(), None);
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
+use log::warn;
+
+use crate::config::{Config, LocationConfig, PolarBehavior, ProviderKind};
+use crate::solar::calculate_sun_times;
+use crate::sun_times::{
+    SUNRISE_SUNSET_ORG_URL,
+    SUNRISESUNSET_IO_URL,
+    SunTimes,
+    Twilight,
+    build_sunrise_sunset_org_url,
+    build_sunrisesunset_url,
+    fetch_sunrise_sunset,
+    fetch_sunrise_sunset_org,
+};
+
+pub trait SunTimesProvider {
+    fn name(&self) -> &'static str;
+    fn sun_times(&self, location: &LocationConfig, date: NaiveDate) -> Result<SunTimes, Box<dyn std::error::Error>>;
+}
+
+pub struct SunriseSunsetIo {
+    pub base_url: String,
+}
+
+impl SunTimesProvider for SunriseSunsetIo {
+    fn name(&self) -> &'static str {
+        "sunrisesunset.io"
+    }
+
+    fn sun_times(&self, location: &LocationConfig, date: NaiveDate) -> Result<SunTimes, Box<dyn std::error::Error>> {
+        fetch_sunrise_sunset(&build_sunrisesunset_url(&self.base_url, location, date))
+    }
+}
+
+pub struct SunriseSunsetOrg {
+    pub base_url: String,
+}
+
+impl SunTimesProvider for SunriseSunsetOrg {
+    fn name(&self) -> &'static str {
+        "sunrise-sunset.org"
+    }
+
+    fn sun_times(&self, location: &LocationConfig, date: NaiveDate) -> Result<SunTimes, Box<dyn std::error::Error>> {
+        fetch_sunrise_sunset_org(&build_sunrise_sunset_org_url(&self.base_url, location, date))
+    }
+}
+
+pub struct Calculated;
+
+impl SunTimesProvider for Calculated {
+    fn name(&self) -> &'static str {
+        "calculated"
+    }
+
+    fn sun_times(&self, location: &LocationConfig, date: NaiveDate) -> Result<SunTimes, Box<dyn std::error::Error>> {
+        calculate_sun_times(location, date)
+    }
+}
+
+// Same sunrise and sunset every day, given in local time
+pub struct FixedSchedule {
+    pub sunrise: NaiveTime,
+    pub sunset: NaiveTime,
+}
+
+impl SunTimesProvider for FixedSchedule {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    fn sun_times(&self, _location: &LocationConfig, date: NaiveDate) -> Result<SunTimes, Box<dyn std::error::Error>> {
+        let to_utc = |time: NaiveTime| {
+            Local.from_local_datetime(&date.and_time(time))
+                .earliest()
+                .map(|datetime| datetime.to_utc())
+                .ok_or_else(|| format!("{} does not exist in local time on {}", time, date))
+        };
+
+        Ok(SunTimes::Regular { sunrise: to_utc(self.sunrise)?, sunset: to_utc(self.sunset)?, twilight: Twilight::default() })
+    }
+}
+
+pub fn build_providers(config: &Config) -> Vec<Box<dyn SunTimesProvider>> {
+    config.sun_times.providers.iter().map(|kind| -> Box<dyn SunTimesProvider> {
+        match kind {
+            ProviderKind::SunriseSunsetIo => Box::new(SunriseSunsetIo { base_url: SUNRISESUNSET_IO_URL.to_string() }),
+            ProviderKind::SunriseSunsetOrg => Box::new(SunriseSunsetOrg { base_url: SUNRISE_SUNSET_ORG_URL.to_string() }),
+            ProviderKind::Calculated => Box::new(Calculated),
+            ProviderKind::Fixed => Box::new(FixedSchedule {
+                sunrise: config.sun_times.fixed_sunrise,
+                sunset: config.sun_times.fixed_sunset,
+            }),
+        }
+    }).collect()
+}
+
+// Tries every provider in order and returns the first successful result,
+// along with the name of the provider it came from
+pub fn fetch_sun_times(
+    providers: &[Box<dyn SunTimesProvider>],
+    location: &LocationConfig,
+    date: NaiveDate,
+) -> Result<(&'static str, SunTimes), Box<dyn std::error::Error>> {
+    for provider in providers {
+        match provider.sun_times(location, date) {
+            Ok(sun_times) => return Ok((provider.name(), sun_times)),
+            Err(error) => warn!("Provider {} failed: {}", provider.name(), error),
+        }
+    }
+
+    Err("No sun_times provider succeeded".into())
+}
+
+// Swaps polar days and nights for the fixed schedule when configured to
+pub fn apply_polar_behavior(sun_times: SunTimes, config: &Config, date: NaiveDate) -> Result<SunTimes, Box<dyn std::error::Error>> {
+    let is_polar = matches!(sun_times, SunTimes::PolarDay | SunTimes::PolarNight);
+    if !is_polar || config.sun_times.polar == PolarBehavior::FollowSun {
+        return Ok(sun_times);
+    }
+
+    let fixed_schedule = FixedSchedule { sunrise: config.sun_times.fixed_sunrise, sunset: config.sun_times.fixed_sunset };
+    fixed_schedule.sun_times(&config.location, date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_test_config;
+
+    #[test]
+    fn test_fetch_sun_times_falls_back_in_order() {
+        let config = get_test_config();
+        let date = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let sunrise = NaiveTime::from_hms_opt(6, 0, 0).unwrap();
+        let sunset = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
+
+        // Nothing listens on port 9, so the first provider always fails
+        let providers: Vec<Box<dyn SunTimesProvider>> = vec![
+            Box::new(SunriseSunsetIo { base_url: "http://127.0.0.1:9".to_string() }),
+            Box::new(FixedSchedule { sunrise, sunset }),
+            Box::new(Calculated),
+        ];
+
+        let (provider, result) = fetch_sun_times(&providers, &config.location, date).unwrap();
+        let expected_result = FixedSchedule { sunrise, sunset }.sun_times(&config.location, date).unwrap();
+
+        assert_eq!(provider, "fixed");
+        assert_eq!(result, expected_result);
+    }
+
+    #[test]
+    fn test_fetch_sun_times_all_failing() {
+        let config = get_test_config();
+        let date = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let providers: Vec<Box<dyn SunTimesProvider>> = vec![
+            Box::new(SunriseSunsetOrg { base_url: "http://127.0.0.1:9".to_string() }),
+        ];
+
+        assert!(fetch_sun_times(&providers, &config.location, date).is_err());
+        assert!(fetch_sun_times(&[], &config.location, date).is_err());
+    }
+
+    #[test]
+    fn test_apply_polar_behavior() {
+        let mut config = get_test_config();
+        let date = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let regular = SunTimes::Regular {
+            sunrise: date.and_hms_opt(6, 0, 0).unwrap().and_utc(),
+            sunset: date.and_hms_opt(18, 0, 0).unwrap().and_utc(),
+            twilight: Twilight::default(),
+        };
+        let fixed = FixedSchedule { sunrise: config.sun_times.fixed_sunrise, sunset: config.sun_times.fixed_sunset }
+            .sun_times(&config.location, date)
+            .unwrap();
+
+        config.sun_times.polar = PolarBehavior::FollowSun;
+        assert_eq!(apply_polar_behavior(SunTimes::PolarDay, &config, date).unwrap(), SunTimes::PolarDay);
+        assert_eq!(apply_polar_behavior(SunTimes::PolarNight, &config, date).unwrap(), SunTimes::PolarNight);
+
+        config.sun_times.polar = PolarBehavior::Fixed;
+        assert_eq!(apply_polar_behavior(SunTimes::PolarDay, &config, date).unwrap(), fixed);
+        assert_eq!(apply_polar_behavior(SunTimes::PolarNight, &config, date).unwrap(), fixed);
+        assert_eq!(apply_polar_behavior(regular.clone(), &config, date).unwrap(), regular);
+    }
+
+    #[test]
+    fn test_build_providers() {
+        let mut config = get_test_config();
+        config.sun_times.providers = vec![
+            ProviderKind::Fixed,
+            ProviderKind::SunriseSunsetOrg,
+            ProviderKind::Calculated,
+            ProviderKind::SunriseSunsetIo,
+        ];
+
+        let names: Vec<&str> = build_providers(&config).iter().map(|provider| provider.name()).collect();
+
+        assert_eq!(names, vec!["fixed", "sunrise-sunset.org", "calculated", "sunrisesunset.io"]);
+    }
+}
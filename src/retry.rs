@@ -0,0 +1,72 @@
+use std::fmt::Display;
+use std::time::Duration;
+use log::warn;
+
+const MAX_ATTEMPTS: u32 = 4;
+// Doubled after every failed attempt: 250ms, 500ms, 1s
+const INITIAL_BACKOFF_IN_MILLISECONDS: u64 = 250;
+
+// Runs operation until it succeeds, fails for good, or runs out of attempts
+pub fn with_retries<T, E: Display>(
+    description: &str,
+    is_transient: impl Fn(&E) -> bool,
+    operation: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    with_backoff(description, is_transient, operation, Duration::from_millis(INITIAL_BACKOFF_IN_MILLISECONDS))
+}
+
+fn with_backoff<T, E: Display>(
+    description: &str,
+    is_transient: impl Fn(&E) -> bool,
+    mut operation: impl FnMut() -> Result<T, E>,
+    initial_backoff: Duration,
+) -> Result<T, E> {
+    let mut backoff = initial_backoff;
+    let mut attempt = 1;
+
+    loop {
+        match operation() {
+            Err(error) if attempt < MAX_ATTEMPTS && is_transient(&error) => {
+                warn!("{} failed (attempt {} of {}), retrying in {:?}: {}", description, attempt, MAX_ATTEMPTS, backoff, error);
+                std::thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_backoff() {
+        let backoff = Duration::from_millis(1);
+
+        let mut calls = 0;
+        let result: Result<u32, String> = with_backoff("Flaky", |_| true, || {
+            calls += 1;
+            if calls < 3 { Err("not yet".to_string()) } else { Ok(calls) }
+        }, backoff);
+        assert_eq!(result, Ok(3));
+
+        let mut calls = 0;
+        let result: Result<(), String> = with_backoff("Broken", |_| true, || {
+            calls += 1;
+            Err("still broken".to_string())
+        }, backoff);
+        assert_eq!(result, Err("still broken".to_string()));
+        assert_eq!(calls, MAX_ATTEMPTS);
+
+        // Permanent errors aren't retried
+        let mut calls = 0;
+        let result: Result<(), String> = with_backoff("Permanent", |error| error != "permanent", || {
+            calls += 1;
+            Err("permanent".to_string())
+        }, backoff);
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
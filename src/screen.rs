@@ -1,170 +1,432 @@
-use chrono::{NaiveTime, Duration};
-use crate::config::Config;
-use crate::sun_times::SunTimes;
+use chrono::{DateTime, Duration, DurationRound, Local, NaiveDate, TimeZone, Utc};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use crate::config::{Alignment, Config, Curve, Interpolation, KeyframeConfig, ScheduleMode, config_for_date};
+use crate::overrides::ScreenOverride;
+use crate::solar::solar_elevation;
+use crate::sun_times::{SolarEvent, SunTimes};
 
-#[derive(Debug, PartialEq)]
+// How steep the sigmoid and exponential curves are, higher is steeper
+const SIGMOID_STEEPNESS: f64 = 10.0;
+const EXPONENTIAL_STEEPNESS: f64 = 4.0;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct ScreenState {
-    pub temperature: String,
-    pub gamma: String,
+    pub temperature: u32,
+    pub gamma: f64,
+}
+
+// Fading into a keyframe's screen state
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Fade {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    to: ScreenState,
+    curve: Curve,
+}
+
+// Without keyframes, the morning and evening transitions make up the
+// schedule: fade into day settings, then into night settings. In clock mode
+// they happen at fixed local times instead of solar events
+fn keyframes(config: &Config) -> Vec<KeyframeConfig> {
+    if !config.screen.keyframes.is_empty() {
+        return config.screen.keyframes.clone();
+    }
+
+    let schedule = &config.schedule;
+    let is_clock = schedule.mode == ScheduleMode::Clock;
+    [
+        (&config.screen.morning, schedule.day_start, day_state(config)),
+        (&config.screen.evening, schedule.night_start, night_state(config)),
+    ]
+        .into_iter()
+        .map(|(transition, time, state)| KeyframeConfig {
+            anchor: (!is_clock).then_some(transition.anchor),
+            time: is_clock.then_some(time),
+            offset_in_minutes: transition.offset_in_minutes,
+            temperature: state.temperature,
+            gamma: state.gamma,
+            duration_in_minutes: transition.duration_in_minutes,
+            align: transition.align,
+            curve: transition.curve,
+        })
+        .collect()
 }
 
-fn calculate_fade_state(is_day: bool, target_time: NaiveTime, sun_times: &SunTimes, config: &Config) -> ScreenState {
-    let fade_duration = Duration::minutes(config.screen.fade_duration_in_minutes);
-    let fade_steps = fade_duration.num_minutes();
+// Twilight events some providers don't know about, or that don't happen
+// this close to the solstice, fall back to sunrise or sunset
+fn fallback_event(event: SolarEvent) -> SolarEvent {
+    match event {
+        SolarEvent::AstronomicalDawn
+        | SolarEvent::NauticalDawn
+        | SolarEvent::CivilDawn
+        | SolarEvent::Sunrise
+        | SolarEvent::GoldenHourEnd => SolarEvent::Sunrise,
+        _ => SolarEvent::Sunset,
+    }
+}
 
-    let temperature_day = config.screen.day_temperature.parse::<i64>().unwrap();
-    let temperature_night = config.screen.night_temperature.parse::<i64>().unwrap();
-    let temperature_delta = (temperature_day - temperature_night).abs();
-    let temperature_step = temperature_delta / fade_steps;
+// The config with the profile in effect on that local date. Profiles are
+// checked when loading the config, so this only falls back to [screen] if
+// something slipped through
+fn config_on(config: &Config, date: NaiveDate) -> Config {
+    config_for_date(config, date).unwrap_or_else(|error| {
+        warn!("{}, using [screen] on {}", error, date);
+        config.clone()
+    })
+}
 
-    let gamma_day = config.screen.day_gamma.parse::<i64>().unwrap();
-    let gamma_night = config.screen.night_gamma.parse::<i64>().unwrap();
-    let gamma_delta = (gamma_day - gamma_night).abs();
+fn local_date(time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&Local).date_naive()
+}
 
-    // Gamma is too small of a range (i.e from 80 to 100), so when we progress
-    // by the minute, chances are the step lands in 0. To circumvent that, I've
-    // decided to convert it to float
-    let gamma_step_unrounded = gamma_delta as f64 / fade_steps as f64;
-    let gamma_step = format!("{:.2}", gamma_step_unrounded).parse::<f64>().unwrap();
+fn fade_into(keyframe: &KeyframeConfig, anchor: DateTime<Utc>, config: &Config) -> Fade {
+    let fade_duration = Duration::minutes(keyframe.duration_in_minutes.unwrap_or(config.screen.fade_duration_in_minutes));
+    let anchor = anchor + Duration::minutes(keyframe.offset_in_minutes);
+    let (start, end) = match keyframe.align {
+        Alignment::Start => (anchor, anchor + fade_duration),
+        Alignment::End => (anchor - fade_duration, anchor),
+        Alignment::Center => (anchor - fade_duration / 2, anchor + fade_duration / 2),
+    };
 
-    let diff_base = if is_day { sun_times.sunset } else { sun_times.sunrise };
-    let difference_in_mins = (diff_base - target_time).num_minutes();
-    let factor  = fade_steps - difference_in_mins;
+    Fade { start, end, to: ScreenState { temperature: keyframe.temperature, gamma: keyframe.gamma }, curve: keyframe.curve }
+}
 
-    let temperature;
-    let gamma;
-    if is_day {
-        temperature = temperature_day - (temperature_step * factor);
-        gamma = gamma_day as f64 - (gamma_step * factor as f64);
-    } else {
-        temperature = temperature_night + (temperature_step * factor);
-        gamma = gamma_night as f64 + (gamma_step * factor as f64);
+// Every fade from two local days before target_time until the day after,
+// sorted. Each day's keyframes follow the profile of that day, so a profile
+// ending at midnight still fades out the evening it started.
+// Sun times are only known for one day, so solar keyframes are shifted by
+// whole days onto the day they end on. This keeps working when sunset falls
+// after midnight UTC, or sunrise before it, and the error it adds (a few
+// minutes at most) is irrelevant for fading a screen. Clock keyframes are
+// placed on each local day instead, so they stay put across DST changes.
+// None when a solar keyframe's event doesn't happen at all (polar days and
+// nights)
+fn fades_around(target_time: DateTime<Utc>, sun_times: Option<&SunTimes>, config: &Config) -> Option<Vec<Fade>> {
+    let target_date = local_date(target_time);
+    let mut fades = Vec::new();
+
+    for day in -2..=1 {
+        let date = target_date + Duration::days(day);
+        let day_config = config_on(config, date);
+
+        for keyframe in keyframes(&day_config) {
+            if let Some(event) = keyframe.anchor {
+                let sun_times = sun_times?;
+                let anchor = sun_times.event(event).or_else(|| {
+                    debug!("No {:?} in sun_times, anchoring to {:?} instead", event, fallback_event(event));
+                    sun_times.event(fallback_event(event))
+                })?;
+                let fade = fade_into(&keyframe, anchor, &day_config);
+                let shift = Duration::days((date - local_date(fade.end)).num_days());
+
+                fades.push(Fade { start: fade.start + shift, end: fade.end + shift, ..fade });
+            } else if let Some(time) = keyframe.time {
+                match Local.from_local_datetime(&date.and_time(time)).earliest() {
+                    Some(anchor) => fades.push(fade_into(&keyframe, anchor.to_utc(), &day_config)),
+                    None => debug!("{} does not exist in local time on {}, skipping", time, date),
+                }
+            }
+        }
     }
 
-    ScreenState { temperature: temperature.to_string(), gamma: gamma.to_string() }
+    fades.sort_by_key(|fade| fade.start);
+    Some(fades)
 }
 
-pub fn calculate_screen_state(target_time: NaiveTime, sun_times: &SunTimes, config: &Config) -> ScreenState {
-    let fade_duration = Duration::minutes(config.screen.fade_duration_in_minutes);
-    let is_day = target_time >= sun_times.sunrise && target_time < sun_times.sunset;
-    let fading_into_day = !is_day &&
-                          target_time > sun_times.sunrise - fade_duration &&
-                          target_time < sun_times.sunrise;
-    let fading_into_night = is_day &&
-                            target_time > sun_times.sunset - fade_duration &&
-                            target_time < sun_times.sunset;
+// The fade target_time is in or last went through, the fade before it, and
+// the next one
+fn surrounding_fades(target_time: DateTime<Utc>, sun_times: Option<&SunTimes>, config: &Config) -> Option<(Fade, Fade, Fade)> {
+    let fades = fades_around(target_time, sun_times, config)?;
 
-    if fading_into_night || fading_into_day {
-        return calculate_fade_state(is_day, target_time, sun_times, config);
+    // At the very start of a fade, the screen is still on the previous setting
+    let next_index = fades.iter().position(|fade| fade.start >= target_time)?;
+    if next_index < 2 {
+        return None;
     }
 
-    if is_day {
-        return ScreenState {
-            temperature: config.screen.day_temperature.clone(),
-            gamma: config.screen.day_gamma.clone(),
-        };
+    Some((fades[next_index - 2], fades[next_index - 1], fades[next_index]))
+}
+
+// Maps the linear progress of a fade (0 to 1) onto the curve. Every curve
+// is monotonic and exactly 0 and 1 at both ends
+fn ease(curve: Curve, progress: f64) -> f64 {
+    match curve {
+        Curve::Linear | Curve::SolarElevation => progress,
+        Curve::Smoothstep => progress * progress * (3.0 - 2.0 * progress),
+        Curve::Sigmoid => {
+            let logistic = |x: f64| 1.0 / (1.0 + (-SIGMOID_STEEPNESS * (x - 0.5)).exp());
+            (logistic(progress) - logistic(0.0)) / (logistic(1.0) - logistic(0.0))
+        }
+        Curve::Exponential => (EXPONENTIAL_STEEPNESS * progress).exp_m1() / EXPONENTIAL_STEEPNESS.exp_m1(),
+    }
+}
+
+fn fade_progress(fade: &Fade, target_time: DateTime<Utc>, config: &Config) -> f64 {
+    let fade_length = (fade.end - fade.start).num_milliseconds() as f64;
+    if fade_length <= 0.0 {
+        return 1.0;
     }
+    let progress = ((target_time - fade.start).num_milliseconds() as f64 / fade_length).clamp(0.0, 1.0);
 
+    if fade.curve != Curve::SolarElevation {
+        return ease(fade.curve, progress);
+    }
+
+    let start_elevation = solar_elevation(&config.location, fade.start);
+    let end_elevation = solar_elevation(&config.location, fade.end);
+    let elevation = solar_elevation(&config.location, target_time);
+    if (end_elevation - start_elevation).abs() < f64::EPSILON {
+        return progress;
+    }
+
+    ((elevation - start_elevation) / (end_elevation - start_elevation)).clamp(0.0, 1.0)
+}
+
+fn calculate_fade_state(from: ScreenState, fade: &Fade, target_time: DateTime<Utc>, config: &Config) -> ScreenState {
+    let to = fade.to;
+    let progress = fade_progress(fade, target_time, config);
+
+    // Written this way so both ends land exactly on the configured values
+    let interpolate = |from: f64, to: f64| from * (1.0 - progress) + to * progress;
+    let (from_temperature, to_temperature) = (from.temperature as f64, to.temperature as f64);
+    let temperature = match config.screen.interpolation {
+        Interpolation::Kelvin => interpolate(from_temperature, to_temperature),
+        Interpolation::Mired => 1_000_000.0 / interpolate(1_000_000.0 / from_temperature, 1_000_000.0 / to_temperature),
+    }.round() as u32;
+    // hyprsunset takes fractional gammas, two decimals are plenty
+    let gamma = (interpolate(from.gamma, to.gamma) * 100.0).round() / 100.0;
+
+    ScreenState { temperature, gamma }
+}
+
+fn day_state(config: &Config) -> ScreenState {
     ScreenState {
-        temperature: config.screen.night_temperature.clone(),
-        gamma: config.screen.night_gamma.clone(),
+        temperature: config.screen.day_temperature,
+        gamma: config.screen.day_gamma,
+    }
+}
+
+fn night_state(config: &Config) -> ScreenState {
+    ScreenState {
+        temperature: config.screen.night_temperature,
+        gamma: config.screen.night_gamma,
+    }
+}
+
+fn scheduled_screen_state(target_time: DateTime<Utc>, sun_times: Option<&SunTimes>, config: &Config) -> ScreenState {
+    let Some((previous_fade, current_fade, _)) = surrounding_fades(target_time, sun_times, config) else {
+        let config = config_on(config, local_date(target_time));
+        return match sun_times {
+            Some(SunTimes::PolarNight) => night_state(&config),
+            _ => day_state(&config),
+        };
+    };
+
+    if target_time < current_fade.end {
+        let config = config_on(config, local_date(target_time));
+        return calculate_fade_state(previous_fade.to, &current_fade, target_time, &config);
+    }
+
+    current_fade.to
+}
+
+// How long the scheduled screen state computed for target_time stays valid:
+// one minute while fading, otherwise until the next fade starts. Polar days
+// and nights last until the sun times for the next day are in
+fn time_until_scheduled_change(target_time: DateTime<Utc>, sun_times: Option<&SunTimes>, config: &Config) -> Duration {
+    let Some((_, current_fade, next_fade)) = surrounding_fades(target_time, sun_times, config) else {
+        return time_until_midnight(target_time);
+    };
+
+    if target_time < current_fade.end {
+        return time_until_next_minute(target_time);
+    }
+
+    // Fade states only start changing one minute into the fade
+    next_fade.start - target_time + Duration::minutes(1).min(next_fade.end - next_fade.start)
+}
+
+fn time_until_next_minute(target_time: DateTime<Utc>) -> Duration {
+    let one_minute = Duration::minutes(1);
+    target_time.duration_trunc(one_minute).unwrap() + one_minute - target_time
+}
+
+fn time_until_midnight(target_time: DateTime<Utc>) -> Duration {
+    let next_midnight = target_time.duration_trunc(Duration::days(1)).unwrap() + Duration::days(1);
+    next_midnight - target_time
+}
+
+// How long fading back from an override that expires at until takes, per
+// the profile of that day
+fn fade_back_duration(until: DateTime<Utc>, config: &Config) -> Duration {
+    Duration::minutes(config_on(config, local_date(until)).screen.fade_duration_in_minutes)
+}
+
+// Once an override expires, the screen fades back into the schedule like it
+// would into any keyframe
+fn override_fade_back(until: DateTime<Utc>, scheduled_state: ScreenState, config: &Config) -> Fade {
+    Fade {
+        start: until,
+        end: until + fade_back_duration(until, config),
+        to: scheduled_state,
+        curve: Curve::Smoothstep,
+    }
+}
+
+// Whether the override no longer affects the screen, fade back included
+pub fn override_is_over(screen_override: &ScreenOverride, target_time: DateTime<Utc>, config: &Config) -> bool {
+    screen_override.until
+        .is_some_and(|until| target_time >= until + fade_back_duration(until, config))
+}
+
+// config is the one loaded, profiles are applied to each day's keyframes here
+pub fn calculate_screen_state(
+    target_time: DateTime<Utc>,
+    sun_times: Option<&SunTimes>,
+    config: &Config,
+    screen_override: Option<&ScreenOverride>,
+) -> ScreenState {
+    let scheduled_state = scheduled_screen_state(target_time, sun_times, config);
+    let Some(screen_override) = screen_override.filter(|screen_override| target_time >= screen_override.from) else {
+        return scheduled_state;
+    };
+
+    match screen_override.until {
+        Some(until) if target_time >= until => {
+            let fade_back = override_fade_back(until, scheduled_state, config);
+            calculate_fade_state(screen_override.screen_state, &fade_back, target_time, &config_on(config, local_date(target_time)))
+        },
+        _ => screen_override.screen_state,
+    }
+}
+
+// How long the screen state computed for target_time stays valid, taking the
+// override into account
+pub fn time_until_next_change(
+    target_time: DateTime<Utc>,
+    sun_times: Option<&SunTimes>,
+    config: &Config,
+    screen_override: Option<&ScreenOverride>,
+) -> Duration {
+    let scheduled_change = time_until_scheduled_change(target_time, sun_times, config);
+    let Some(screen_override) = screen_override else {
+        return scheduled_change;
+    };
+
+    if target_time < screen_override.from {
+        return scheduled_change.min(screen_override.from - target_time);
+    }
+
+    match screen_override.until {
+        None => time_until_midnight(target_time),
+        Some(until) if target_time < until => until - target_time,
+        Some(_) if !override_is_over(screen_override, target_time, config) => time_until_next_minute(target_time),
+        Some(_) => scheduled_change,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::get_test_config;
+    use chrono::{NaiveDate, TimeZone};
+    use crate::config::{ProfileConfig, ProfileRule, ScheduleMode, TransitionConfig, get_test_config};
+    use crate::solar::calculate_sun_times;
+    use crate::sun_times::Twilight;
+
+    fn at(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 21, hour, minute, second).unwrap()
+    }
 
     #[test]
     fn test_calculate_screen_state() {
         let config = get_test_config();
-        let sunrise = NaiveTime::from_hms_opt(6, 0, 0).unwrap();
-        let sunset = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
-        let sun_times = SunTimes { sunrise, sunset };
+        let sunrise = at(6, 0, 0);
+        let sunset = at(18, 0, 0);
+        let sun_times = SunTimes::Regular { sunrise, sunset, twilight: Twilight::default() };
         let test_cases = vec![
             (
-                NaiveTime::from_hms_opt(02, 00, 00).unwrap(),
-                config.screen.night_temperature.clone(),
-                config.screen.night_gamma.clone(),
+                at(2, 0, 0),
+                config.screen.night_temperature,
+                config.screen.night_gamma,
                 "Before dawn"
             ),
             (
-                NaiveTime::from_hms_opt(05, 00, 00).unwrap(),
-                config.screen.night_temperature.clone(),
-                config.screen.night_gamma.clone(),
+                at(5, 0, 0),
+                config.screen.night_temperature,
+                config.screen.night_gamma,
                 "Dawn fade at 0 mins"
             ),
             (
-                NaiveTime::from_hms_opt(05, 01, 00).unwrap(),
-                "2853".to_string(),
-                "80.33".to_string(),
+                at(5, 1, 0),
+                2853,
+                80.33,
                 "Dawn fade at 1 min"
             ),
             (
-                NaiveTime::from_hms_opt(05, 30, 00).unwrap(),
-                "4390".to_string(), // 2800 + (53 * 30)
-                "89.9".to_string(), // 80.0 + (0.33 * 30)
+                at(5, 30, 0),
+                4400, // 2800 + (3200 / 2)
+                90.0, // 80.0 + (20 / 2)
                 "Dawn fade at 30 mins"
             ),
             (
-                NaiveTime::from_hms_opt(05, 59, 00).unwrap(),
-                "5927".to_string(),
-                "99.47".to_string(),
+                at(5, 59, 0),
+                5947,
+                99.67,
                 "Dawn fade at last minute"
             ),
             (
-                NaiveTime::from_hms_opt(06, 00, 00).unwrap(),
-                config.screen.day_temperature.clone(),
-                config.screen.day_gamma.clone(),
+                at(6, 0, 0),
+                config.screen.day_temperature,
+                config.screen.day_gamma,
                 "Sunrise"
             ),
             (
-                NaiveTime::from_hms_opt(10, 00, 00).unwrap(),
-                config.screen.day_temperature.clone(),
-                config.screen.day_gamma.clone(),
+                at(10, 0, 0),
+                config.screen.day_temperature,
+                config.screen.day_gamma,
                 "Day"
             ),
             (
-                NaiveTime::from_hms_opt(17, 00, 00).unwrap(),
-                config.screen.day_temperature.clone(),
-                config.screen.day_gamma.clone(),
+                at(17, 0, 0),
+                config.screen.day_temperature,
+                config.screen.day_gamma,
                 "Evening fade at 0 mins"
             ),
             (
-                NaiveTime::from_hms_opt(17, 01, 00).unwrap(),
-                "5947".to_string(),
-                "99.67".to_string(),
+                at(17, 1, 0),
+                5947,
+                99.67,
                 "Evening fade at 1 min"
             ),
             (
-                NaiveTime::from_hms_opt(17, 30, 00).unwrap(),
-                "4410".to_string(), // 6000 - (53 * 30)
-                "90.1".to_string(), // 100 - (0.33 * 30)
+                at(17, 30, 0),
+                4400, // 6000 - (3200 / 2)
+                90.0, // 100 - (20 / 2)
                 "Evening fade at 30 mins"
             ),
             (
-                NaiveTime::from_hms_opt(17, 59, 00).unwrap(),
-                "2873".to_string(),
-                "80.53".to_string(),
+                at(17, 59, 0),
+                2853,
+                80.33,
                 "Evening fade at last min"
             ),
             (
-                NaiveTime::from_hms_opt(18, 00, 00).unwrap(),
-                config.screen.night_temperature.clone(),
-                config.screen.night_gamma.clone(),
+                at(18, 0, 0),
+                config.screen.night_temperature,
+                config.screen.night_gamma,
                 "Sunset"
             ),
             (
-                NaiveTime::from_hms_opt(22, 00, 00).unwrap(),
-                config.screen.night_temperature.clone(),
-                config.screen.night_gamma.clone(),
+                at(22, 0, 0),
+                config.screen.night_temperature,
+                config.screen.night_gamma,
                 "Night"
             ),
         ];
 
         for (time, expected_temperature, expected_gamma, description) in test_cases {
-            let screen_state = calculate_screen_state(time, &sun_times, &config);
+            let screen_state = calculate_screen_state(time, Some(&sun_times), &config, None);
             let expected_screen_state = ScreenState {
                 temperature: expected_temperature,
                 gamma: expected_gamma,
@@ -172,4 +434,369 @@ mod tests {
             assert_eq!(screen_state, expected_screen_state, "Screen state failed for {}", description);
         }
     }
+
+    #[test]
+    fn test_time_until_next_change() {
+        let config = get_test_config();
+        let sunrise = at(6, 0, 0);
+        let sunset = at(18, 0, 0);
+        let sun_times = SunTimes::Regular { sunrise, sunset, twilight: Twilight::default() };
+        let test_cases = vec![
+            (at(2, 0, 0), Duration::minutes(181), "Before dawn"),
+            (at(5, 0, 0), Duration::minutes(1), "Dawn fade start"),
+            (at(5, 30, 20), Duration::seconds(40), "During dawn fade"),
+            (at(5, 59, 0), Duration::minutes(1), "Dawn fade at last minute"),
+            (at(6, 0, 0), Duration::minutes(661), "Sunrise"),
+            (at(17, 30, 0), Duration::minutes(1), "During evening fade"),
+            (at(18, 0, 0), Duration::minutes(661), "Sunset"),
+            (at(23, 0, 0), Duration::minutes(361), "Night, across midnight"),
+        ];
+
+        for (time, expected_duration, description) in test_cases {
+            let duration = time_until_next_change(time, Some(&sun_times), &config, None);
+            assert_eq!(duration, expected_duration, "Next change failed for {}", description);
+        }
+    }
+
+    #[test]
+    fn test_screen_override() {
+        let config = get_test_config();
+        let sun_times = SunTimes::Regular { sunrise: at(6, 0, 0), sunset: at(18, 0, 0), twilight: Twilight::default() };
+        let day = ScreenState { temperature: 6500, gamma: 100.0 };
+        let night = night_state(&config);
+        let screen_override = ScreenOverride { screen_state: day, from: at(20, 0, 0), until: Some(at(22, 0, 0)) };
+        let test_cases = vec![
+            (at(19, 0, 0), night, Duration::minutes(60), "Before the override"),
+            (at(21, 0, 0), day, Duration::minutes(60), "During the override"),
+            (at(22, 0, 0), day, Duration::minutes(1), "Expiry"),
+            (at(22, 30, 20), ScreenState { temperature: 4619, gamma: 89.83 }, Duration::seconds(40), "Fading back"),
+            (at(23, 0, 0), night, Duration::minutes(361), "Back on schedule"),
+        ];
+
+        for (time, expected_screen_state, expected_duration, description) in test_cases {
+            assert_eq!(calculate_screen_state(time, Some(&sun_times), &config, Some(&screen_override)), expected_screen_state, "Screen state failed for {}", description);
+            assert_eq!(time_until_next_change(time, Some(&sun_times), &config, Some(&screen_override)), expected_duration, "Next change failed for {}", description);
+        }
+        assert!(!override_is_over(&screen_override, at(22, 59, 0), &config));
+        assert!(override_is_over(&screen_override, at(23, 0, 0), &config));
+
+        // Paused until resumed
+        let paused = ScreenOverride { until: None, ..screen_override };
+        assert_eq!(calculate_screen_state(at(23, 0, 0), Some(&sun_times), &config, Some(&paused)), day);
+        assert_eq!(time_until_next_change(at(23, 0, 0), Some(&sun_times), &config, Some(&paused)), Duration::minutes(60));
+        assert!(!override_is_over(&paused, at(23, 0, 0), &config));
+    }
+
+    #[test]
+    fn test_polar_screen_state() {
+        let config = get_test_config();
+        let test_cases = vec![
+            (SunTimes::PolarDay, config.screen.day_temperature, config.screen.day_gamma, "Polar day"),
+            (SunTimes::PolarNight, config.screen.night_temperature, config.screen.night_gamma, "Polar night"),
+        ];
+
+        for (sun_times, expected_temperature, expected_gamma, description) in test_cases {
+            for hour in [0, 6, 12, 18, 23] {
+                let time = at(hour, 0, 0);
+                let screen_state = calculate_screen_state(time, Some(&sun_times), &config, None);
+                let expected_screen_state = ScreenState { temperature: expected_temperature, gamma: expected_gamma };
+                assert_eq!(screen_state, expected_screen_state, "Screen state failed for {} at {}", description, time);
+            }
+
+            let time = at(22, 30, 0);
+            assert_eq!(time_until_next_change(time, Some(&sun_times), &config, None), Duration::minutes(90), "{}", description);
+        }
+    }
+
+    #[test]
+    fn test_calculate_screen_state_with_twilight_anchors() {
+        let mut config = get_test_config();
+        config.screen.morning = TransitionConfig { anchor: SolarEvent::CivilDawn, align: Alignment::Start, duration_in_minutes: None, offset_in_minutes: 0, curve: Curve::Linear };
+        config.screen.evening = TransitionConfig { anchor: SolarEvent::GoldenHour, align: Alignment::Start, duration_in_minutes: None, offset_in_minutes: 0, curve: Curve::Linear };
+        let sun_times = SunTimes::Regular {
+            sunrise: at(6, 0, 0),
+            sunset: at(18, 0, 0),
+            twilight: Twilight {
+                civil_dawn: Some(at(5, 20, 0)),
+                golden_hour: Some(at(16, 50, 0)),
+                ..Twilight::default()
+            },
+        };
+        let test_cases = vec![
+            (at(5, 20, 0), (2800, 80.0), "Civil dawn, fade start"),
+            (at(5, 50, 0), (4400, 90.0), "Dawn fade at 30 mins"),
+            (at(6, 20, 0), (6000, 100.0), "Dawn fade end"),
+            (at(16, 50, 0), (6000, 100.0), "Golden hour, fade start"),
+            (at(17, 20, 0), (4400, 90.0), "Evening fade at 30 mins"),
+            (at(17, 50, 0), (2800, 80.0), "Evening fade end"),
+        ];
+
+        for (time, (expected_temperature, expected_gamma), description) in test_cases {
+            let screen_state = calculate_screen_state(time, Some(&sun_times), &config, None);
+            let expected_screen_state = ScreenState { temperature: expected_temperature, gamma: expected_gamma };
+            assert_eq!(screen_state, expected_screen_state, "Screen state failed for {}", description);
+        }
+
+        // Providers that don't know about nautical dusk fall back to sunset
+        config.screen.evening = TransitionConfig { anchor: SolarEvent::NauticalDusk, align: Alignment::Start, duration_in_minutes: None, offset_in_minutes: 0, curve: Curve::Linear };
+        let screen_state = calculate_screen_state(at(18, 30, 0), Some(&sun_times), &config, None);
+        assert_eq!(screen_state, ScreenState { temperature: 4400, gamma: 90.0 });
+    }
+
+    #[test]
+    fn test_asymmetric_fades() {
+        let mut config = get_test_config();
+        // Warming up centered on sunrise, cooling down from 30 minutes before
+        // sunset until 45 minutes after it
+        config.screen.morning = TransitionConfig {
+            anchor: SolarEvent::Sunrise,
+            align: Alignment::Center,
+            duration_in_minutes: Some(20),
+            offset_in_minutes: 0,
+            curve: Curve::Linear,
+        };
+        config.screen.evening = TransitionConfig {
+            anchor: SolarEvent::Sunset,
+            align: Alignment::Start,
+            duration_in_minutes: Some(75),
+            offset_in_minutes: -30,
+            curve: Curve::Linear,
+        };
+        let sun_times = SunTimes::Regular { sunrise: at(6, 0, 0), sunset: at(18, 0, 0), twilight: Twilight::default() };
+        let test_cases = vec![
+            (at(5, 50, 0), (2800, 80.0), "Morning fade start"),
+            (at(6, 0, 0), (4400, 90.0), "Sunrise, halfway through"),
+            (at(6, 10, 0), (6000, 100.0), "Morning fade end"),
+            (at(17, 30, 0), (6000, 100.0), "Evening fade start"),
+            (at(18, 0, 0), (4720, 92.0), "Sunset, 30 of 75 minutes in"),
+            (at(18, 45, 0), (2800, 80.0), "Evening fade end"),
+        ];
+
+        for (time, (expected_temperature, expected_gamma), description) in test_cases {
+            let screen_state = calculate_screen_state(time, Some(&sun_times), &config, None);
+            let expected_screen_state = ScreenState { temperature: expected_temperature, gamma: expected_gamma };
+            assert_eq!(screen_state, expected_screen_state, "Screen state failed for {}", description);
+        }
+
+        assert_eq!(time_until_next_change(at(12, 0, 0), Some(&sun_times), &config, None), Duration::minutes(331));
+        assert_eq!(time_until_next_change(at(20, 0, 0), Some(&sun_times), &config, None), Duration::minutes(591));
+    }
+
+    #[test]
+    fn test_keyframes() {
+        let mut config = get_test_config();
+        let keyframe = |anchor: Option<SolarEvent>, time: Option<DateTime<Utc>>, temperature: u32, gamma: f64| KeyframeConfig {
+            anchor,
+            time: time.map(|time| time.with_timezone(&Local).time()),
+            offset_in_minutes: 0,
+            temperature,
+            gamma,
+            duration_in_minutes: None,
+            align: Alignment::End,
+            curve: Curve::Linear,
+        };
+        // Day, evening and bedtime, the latter given in local time
+        config.screen.keyframes = vec![
+            keyframe(Some(SolarEvent::Sunrise), None, 6000, 100.0),
+            keyframe(Some(SolarEvent::Sunset), None, 3400, 90.0),
+            keyframe(None, Some(at(21, 0, 0)), 1900, 60.0),
+        ];
+        let sun_times = SunTimes::Regular { sunrise: at(6, 0, 0), sunset: at(18, 0, 0), twilight: Twilight::default() };
+        let test_cases = vec![
+            (at(5, 30, 0), (3950, 80.0), "Fading from bedtime into day"),
+            (at(12, 0, 0), (6000, 100.0), "Day"),
+            (at(17, 30, 0), (4700, 95.0), "Fading into evening"),
+            (at(18, 30, 0), (3400, 90.0), "Evening"),
+            (at(20, 30, 0), (2650, 75.0), "Fading into bedtime"),
+            (at(23, 0, 0), (1900, 60.0), "Bedtime"),
+        ];
+
+        for (time, (expected_temperature, expected_gamma), description) in test_cases {
+            let screen_state = calculate_screen_state(time, Some(&sun_times), &config, None);
+            let expected_screen_state = ScreenState { temperature: expected_temperature, gamma: expected_gamma };
+            assert_eq!(screen_state, expected_screen_state, "Screen state failed for {}", description);
+        }
+
+        assert_eq!(time_until_next_change(at(18, 30, 0), Some(&sun_times), &config, None), Duration::minutes(91));
+
+        // Solar keyframes can't be placed during polar days, clock ones can
+        assert_eq!(calculate_screen_state(at(23, 0, 0), Some(&SunTimes::PolarDay), &config, None), day_state(&config));
+        config.screen.keyframes = vec![
+            keyframe(None, Some(at(7, 0, 0)), 6000, 100.0),
+            keyframe(None, Some(at(21, 0, 0)), 1900, 60.0),
+        ];
+        assert_eq!(calculate_screen_state(at(23, 0, 0), Some(&SunTimes::PolarDay), &config, None), ScreenState { temperature: 1900, gamma: 60.0 });
+        assert_eq!(calculate_screen_state(at(12, 0, 0), Some(&SunTimes::PolarDay), &config, None), ScreenState { temperature: 6000, gamma: 100.0 });
+    }
+
+    #[test]
+    fn test_clock_schedule() {
+        let mut config = get_test_config();
+        config.schedule.mode = ScheduleMode::Clock;
+        config.schedule.day_start = at(14, 0, 0).with_timezone(&Local).time();
+        config.schedule.night_start = at(2, 0, 0).with_timezone(&Local).time();
+        let test_cases = vec![
+            (at(13, 30, 0), (4400, 90.0), "Fading into day"),
+            (at(20, 0, 0), (6000, 100.0), "Day"),
+            (at(1, 30, 0), (4400, 90.0), "Fading into night"),
+            (at(8, 0, 0), (2800, 80.0), "Night"),
+        ];
+
+        // No sun times needed at all
+        for (time, (expected_temperature, expected_gamma), description) in test_cases {
+            let screen_state = calculate_screen_state(time, None, &config, None);
+            let expected_screen_state = ScreenState { temperature: expected_temperature, gamma: expected_gamma };
+            assert_eq!(screen_state, expected_screen_state, "Screen state failed for {}", description);
+        }
+
+        assert_eq!(time_until_next_change(at(8, 0, 0), None, &config, None), Duration::minutes(301));
+    }
+
+    #[test]
+    fn test_profile_boundary() {
+        let mut config = get_test_config();
+        config.schedule.mode = ScheduleMode::Clock;
+        config.profiles.insert("school_night".to_string(), ProfileConfig {
+            screen: toml::from_str("night_temperature = 2400").unwrap(),
+        });
+        config.profile_rules.push(ProfileRule {
+            profile: "school_night".to_string(),
+            weekdays: Vec::new(),
+            from: None,
+            to: None,
+            dates: vec![NaiveDate::from_ymd_opt(2025, 6, 21).unwrap()],
+        });
+        let local = |day: u32, hour: u32, minute: u32| Local.with_ymd_and_hms(2025, 6, day, hour, minute, 0).unwrap().to_utc();
+        let test_cases = vec![
+            (local(21, 18, 30), (4200, 90.0), "Fading into the profile's night"),
+            (local(21, 23, 59), (2400, 80.0), "Night, before midnight"),
+            (local(22, 0, 1), (2400, 80.0), "Night, after midnight the profile's night goes on"),
+            (local(22, 6, 30), (4200, 90.0), "Fading from the profile's night into day"),
+            (local(22, 18, 30), (4400, 90.0), "Fading into the default night"),
+            (local(22, 20, 0), (2800, 80.0), "Default night"),
+        ];
+
+        for (time, (expected_temperature, expected_gamma), description) in test_cases {
+            let screen_state = calculate_screen_state(time, None, &config, None);
+            let expected_screen_state = ScreenState { temperature: expected_temperature, gamma: expected_gamma };
+            assert_eq!(screen_state, expected_screen_state, "Screen state failed for {}", description);
+        }
+
+        // Nothing changes at midnight
+        assert_eq!(time_until_next_change(local(21, 23, 59), None, &config, None), Duration::minutes(6 * 60 + 2));
+    }
+
+    #[test]
+    fn test_fade_curves() {
+        let curves = [Curve::Linear, Curve::Smoothstep, Curve::Sigmoid, Curve::Exponential, Curve::SolarElevation];
+        let config = get_test_config();
+        let sun_times = calculate_sun_times(&config.location, NaiveDate::from_ymd_opt(2025, 6, 21).unwrap()).unwrap();
+        let SunTimes::Regular { sunrise, sunset, .. } = sun_times else {
+            panic!("Berlin should have a sunrise and a sunset");
+        };
+
+        for curve in curves {
+            assert_eq!(ease(curve, 0.0), 0.0, "{:?} start", curve);
+            assert_eq!(ease(curve, 1.0), 1.0, "{:?} end", curve);
+
+            let morning = Fade { start: sunrise - Duration::hours(1), end: sunrise, to: day_state(&config), curve };
+            let evening = Fade { start: sunset - Duration::hours(1), end: sunset, to: night_state(&config), curve };
+
+            for (from, fade, into_day) in [(night_state(&config), morning, true), (day_state(&config), evening, false)] {
+                assert_eq!(calculate_fade_state(from, &fade, fade.start, &config), from, "{:?} from", curve);
+                assert_eq!(calculate_fade_state(from, &fade, fade.end, &config), fade.to, "{:?} to", curve);
+
+                let mut previous = from;
+                for second in (0..=3600).step_by(30) {
+                    let state = calculate_fade_state(from, &fade, fade.start + Duration::seconds(second), &config);
+                    if into_day {
+                        assert!(state.temperature >= previous.temperature && state.gamma >= previous.gamma, "{:?} morning at {}s", curve, second);
+                    } else {
+                        assert!(state.temperature <= previous.temperature && state.gamma <= previous.gamma, "{:?} evening at {}s", curve, second);
+                    }
+                    previous = state;
+                }
+            }
+        }
+
+        // Halfway through, symmetric curves are halfway between both settings
+        assert_eq!(ease(Curve::Smoothstep, 0.5), 0.5);
+        assert!((ease(Curve::Sigmoid, 0.5) - 0.5).abs() < 1e-9);
+        assert!(ease(Curve::Exponential, 0.5) < 0.5);
+    }
+
+    #[test]
+    fn test_mired_interpolation() {
+        let mut config = get_test_config();
+        let fade = Fade { start: at(5, 0, 0), end: at(6, 0, 0), to: day_state(&config), curve: Curve::Linear };
+        let test_cases = vec![
+            // Minutes into the fade, Kelvin, mired
+            (0, 2800, 2800),
+            (15, 3600, 3231), // 1e6 / (357.14 - (190.48 / 4))
+            (30, 4400, 3818), // 1e6 / ((357.14 + 166.67) / 2)
+            (45, 5200, 4667),
+            (60, 6000, 6000),
+        ];
+
+        for (minutes, kelvin_temperature, mired_temperature) in test_cases {
+            let time = fade.start + Duration::minutes(minutes);
+
+            config.screen.interpolation = Interpolation::Kelvin;
+            let kelvin = calculate_fade_state(night_state(&config), &fade, time, &config);
+            config.screen.interpolation = Interpolation::Mired;
+            let mired = calculate_fade_state(night_state(&config), &fade, time, &config);
+
+            assert_eq!(kelvin.temperature, kelvin_temperature, "Kelvin at {} mins", minutes);
+            assert_eq!(mired.temperature, mired_temperature, "Mired at {} mins", minutes);
+            assert_eq!(kelvin.gamma, mired.gamma, "Gamma at {} mins", minutes);
+        }
+
+        // Steps are even in mireds, so most of the Kelvin change happens at
+        // the cool end
+        let mireds = |temperature: u32| 1_000_000.0 / temperature as f64;
+        let first_half = mireds(2800) - mireds(3818);
+        let second_half = mireds(3818) - mireds(6000);
+        assert!((first_half - second_half).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_calculate_screen_state_across_midnight() {
+        let config = get_test_config();
+        let date = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let utc = |day_offset: i64, hour: u32, minute: u32| {
+            (date + Duration::days(day_offset)).and_hms_opt(hour, minute, 0).unwrap().and_utc()
+        };
+        let day = (config.screen.day_temperature, config.screen.day_gamma);
+        let night = (config.screen.night_temperature, config.screen.night_gamma);
+
+        // Los Angeles (UTC-7): sunrise 05:42, sunset 20:08 local time, so
+        // sunset falls on the next day in UTC
+        let los_angeles = SunTimes::Regular { sunrise: utc(0, 12, 42), sunset: utc(1, 3, 8), twilight: Twilight::default() };
+        // Sydney (UTC+10): sunrise 07:00, sunset 16:54 local time, so
+        // sunrise falls on the previous day in UTC
+        let sydney = SunTimes::Regular { sunrise: utc(-1, 21, 0), sunset: utc(0, 6, 54), twilight: Twilight::default() };
+
+        let test_cases = vec![
+            (&los_angeles, utc(0, 2, 0), day, "Los Angeles, evening before sunset"),
+            (&los_angeles, utc(0, 5, 0), night, "Los Angeles, night"),
+            (&los_angeles, utc(0, 12, 12), (4400, 90.0), "Los Angeles, dawn fade"),
+            (&los_angeles, utc(0, 18, 0), day, "Los Angeles, noon"),
+            (&los_angeles, utc(1, 2, 38), (4400, 90.0), "Los Angeles, evening fade"),
+            (&los_angeles, utc(1, 4, 0), night, "Los Angeles, next night"),
+            (&sydney, utc(0, 2, 0), day, "Sydney, noon"),
+            (&sydney, utc(0, 6, 24), (4400, 90.0), "Sydney, evening fade"),
+            (&sydney, utc(0, 12, 0), night, "Sydney, night"),
+            (&sydney, utc(0, 20, 30), (4400, 90.0), "Sydney, dawn fade"),
+            (&sydney, utc(0, 21, 30), day, "Sydney, next morning"),
+        ];
+
+        for (sun_times, time, (expected_temperature, expected_gamma), description) in test_cases {
+            let screen_state = calculate_screen_state(time, Some(sun_times), &config, None);
+            let expected_screen_state = ScreenState { temperature: expected_temperature, gamma: expected_gamma };
+            assert_eq!(screen_state, expected_screen_state, "Screen state failed for {}", description);
+        }
+
+        assert_eq!(time_until_next_change(utc(0, 5, 0), Some(&los_angeles), &config, None), Duration::minutes(6 * 60 + 43));
+        assert_eq!(time_until_next_change(utc(0, 12, 0), Some(&sydney), &config, None), Duration::minutes(8 * 60 + 1));
+    }
 }
@@ -0,0 +1,241 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::config::LocationConfig;
+use crate::sun_times::{SunTimes, Twilight};
+
+// Zenith angle used by NOAA for sunrise/sunset: 90° plus the sun's apparent
+// radius and the average atmospheric refraction at the horizon
+const SUNRISE_ZENITH_DEGREES: f64 = 90.833;
+// The sun 6°, 12° and 18° below the horizon
+const CIVIL_TWILIGHT_ZENITH_DEGREES: f64 = 96.0;
+const NAUTICAL_TWILIGHT_ZENITH_DEGREES: f64 = 102.0;
+const ASTRONOMICAL_TWILIGHT_ZENITH_DEGREES: f64 = 108.0;
+// The sun 6° above the horizon
+const GOLDEN_HOUR_ZENITH_DEGREES: f64 = 84.0;
+
+// Based on NOAA's solar calculator
+// https://gml.noaa.gov/grad/solcalc/calcdetails.html
+// Accuracy is within a couple of minutes, which is plenty for fading a screen
+pub fn calculate_sun_times(location: &LocationConfig, date: NaiveDate) -> Result<SunTimes, Box<dyn std::error::Error>> {
+    let latitude = location.latitude;
+    let longitude = location.longitude;
+
+    let sunrise_in_minutes = match event_in_minutes(latitude, longitude, date, SUNRISE_ZENITH_DEGREES, true) {
+        Ok(minutes) => minutes,
+        Err(polar) => return Ok(polar),
+    };
+    let sunset_in_minutes = match event_in_minutes(latitude, longitude, date, SUNRISE_ZENITH_DEGREES, false) {
+        Ok(minutes) => minutes,
+        Err(polar) => return Ok(polar),
+    };
+
+    let twilight_event = |zenith: f64, is_morning: bool| {
+        event_in_minutes(latitude, longitude, date, zenith, is_morning)
+            .ok()
+            .map(|minutes| minutes_to_datetime(date, minutes))
+    };
+
+    Ok(SunTimes::Regular {
+        sunrise: minutes_to_datetime(date, sunrise_in_minutes),
+        sunset: minutes_to_datetime(date, sunset_in_minutes),
+        twilight: Twilight {
+            astronomical_dawn: twilight_event(ASTRONOMICAL_TWILIGHT_ZENITH_DEGREES, true),
+            nautical_dawn: twilight_event(NAUTICAL_TWILIGHT_ZENITH_DEGREES, true),
+            civil_dawn: twilight_event(CIVIL_TWILIGHT_ZENITH_DEGREES, true),
+            golden_hour_end: twilight_event(GOLDEN_HOUR_ZENITH_DEGREES, true),
+            golden_hour: twilight_event(GOLDEN_HOUR_ZENITH_DEGREES, false),
+            civil_dusk: twilight_event(CIVIL_TWILIGHT_ZENITH_DEGREES, false),
+            nautical_dusk: twilight_event(NAUTICAL_TWILIGHT_ZENITH_DEGREES, false),
+            astronomical_dusk: twilight_event(ASTRONOMICAL_TWILIGHT_ZENITH_DEGREES, false),
+        },
+    })
+}
+
+// Angle of the sun above the horizon (negative below it) in degrees, without
+// accounting for atmospheric refraction
+pub fn solar_elevation(location: &LocationConfig, time: DateTime<Utc>) -> f64 {
+    let date = time.date_naive();
+    let minutes = (time - date.and_hms_opt(0, 0, 0).unwrap().and_utc()).num_seconds() as f64 / 60.0;
+    let (equation_of_time, declination) = solar_position(date, minutes);
+
+    let true_solar_time = minutes + equation_of_time + 4.0 * location.longitude;
+    let hour_angle = (true_solar_time / 4.0 - 180.0).to_radians();
+    let latitude = location.latitude.to_radians();
+    let cos_zenith = latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+
+    90.0 - cos_zenith.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+// Minutes after midnight UTC at which the sun crosses the given zenith angle,
+// in the morning or in the evening (possibly negative or past 24h, for events
+// on the neighbouring UTC day), or the polar condition when it never does.
+//
+// The declination moves noticeably within a day around the equinoxes, so the
+// calculation is repeated with the fractional year taken at the previous
+// estimate
+fn event_in_minutes(latitude: f64, longitude: f64, date: NaiveDate, zenith: f64, is_morning: bool) -> Result<f64, SunTimes> {
+    let mut minutes = 720.0;
+
+    for _ in 0..2 {
+        let (equation_of_time, declination) = solar_position(date, minutes);
+
+        let latitude_rad = latitude.to_radians();
+        let cos_hour_angle = zenith.to_radians().cos() / (latitude_rad.cos() * declination.cos())
+            - latitude_rad.tan() * declination.tan();
+
+        if cos_hour_angle > 1.0 {
+            return Err(SunTimes::PolarNight);
+        }
+        if cos_hour_angle < -1.0 {
+            return Err(SunTimes::PolarDay);
+        }
+
+        let hour_angle = cos_hour_angle.acos().to_degrees();
+        let signed_hour_angle = if is_morning { hour_angle } else { -hour_angle };
+        minutes = 720.0 - 4.0 * (longitude + signed_hour_angle) - equation_of_time;
+    }
+
+    Ok(minutes)
+}
+
+// Equation of time (in minutes) and solar declination (in radians), using
+// the Julian century based formulas from NOAA's solar calculator
+fn solar_position(date: NaiveDate, minutes: f64) -> (f64, f64) {
+    let j2000 = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    let days_since_j2000 = (date - j2000).num_days() as f64 + minutes / 1440.0 - 0.5;
+    let t = days_since_j2000 / 36525.0;
+
+    let geometric_mean_longitude = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0).to_radians();
+    let mean_anomaly = (357.52911 + t * (35999.05029 - 0.0001537 * t)).to_radians();
+    let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+
+    let equation_of_center = mean_anomaly.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * mean_anomaly).sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * mean_anomaly).sin() * 0.000289;
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_longitude = (geometric_mean_longitude.to_degrees() + equation_of_center - 0.00569 - 0.00478 * omega.sin()).to_radians();
+
+    let mean_obliquity = 23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+    let declination = (obliquity.sin() * apparent_longitude.sin()).asin();
+
+    let y = (obliquity / 2.0).tan().powi(2);
+    let equation_of_time = 4.0 * (
+        y * (2.0 * geometric_mean_longitude).sin()
+        - 2.0 * eccentricity * mean_anomaly.sin()
+        + 4.0 * eccentricity * y * mean_anomaly.sin() * (2.0 * geometric_mean_longitude).cos()
+        - 0.5 * y * y * (4.0 * geometric_mean_longitude).sin()
+        - 1.25 * eccentricity * eccentricity * (2.0 * mean_anomaly).sin()
+    ).to_degrees();
+
+    (equation_of_time, declination)
+}
+
+fn minutes_to_datetime(date: NaiveDate, minutes: f64) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+
+    midnight + Duration::seconds((minutes * 60.0).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: DateTime<Utc>, expected: DateTime<Utc>, description: &str) {
+        let difference = (actual - expected).num_minutes().abs();
+        assert!(difference <= 2, "{} off by {} minutes: {} vs {}", description, difference, actual, expected);
+    }
+
+    #[test]
+    fn test_calculate_sun_times() {
+        let test_cases = vec![
+            // Berlin, summer solstice: 04:43 / 21:33 CEST
+            (52.52, 13.40, NaiveDate::from_ymd_opt(2025, 6, 21).unwrap(), (2, 43), (19, 33), "Berlin summer"),
+            // Berlin, winter solstice: 08:15 / 15:54 CET
+            (52.52, 13.40, NaiveDate::from_ymd_opt(2025, 12, 21).unwrap(), (7, 15), (14, 54), "Berlin winter"),
+            // New York, spring equinox: 06:59 / 19:10 EDT, sunset is past 23:00 UTC
+            (40.71, -74.01, NaiveDate::from_ymd_opt(2025, 3, 20).unwrap(), (10, 59), (23, 10), "New York"),
+            // Los Angeles, summer solstice: 05:42 / 20:08 PDT, sunset is on the next day in UTC
+            (34.05, -118.24, NaiveDate::from_ymd_opt(2025, 6, 21).unwrap(), (12, 42), (27, 8), "Los Angeles"),
+            // Sydney, winter solstice: 07:00 / 16:54 AEST, sunrise is on the previous day in UTC
+            (-33.87, 151.21, NaiveDate::from_ymd_opt(2025, 6, 21).unwrap(), (-3, 0), (6, 54), "Sydney"),
+        ];
+        let utc = |date: NaiveDate, (hour, minute): (i64, i64)| {
+            date.and_hms_opt(0, 0, 0).unwrap().and_utc() + Duration::hours(hour) + Duration::minutes(minute)
+        };
+
+        for (latitude, longitude, date, sunrise, sunset, description) in test_cases {
+            let location = LocationConfig { latitude, longitude };
+            let SunTimes::Regular { sunrise: actual_sunrise, sunset: actual_sunset, .. } = calculate_sun_times(&location, date).unwrap() else {
+                panic!("{} should have a sunrise and a sunset", description);
+            };
+
+            assert_close(actual_sunrise, utc(date, sunrise), description);
+            assert_close(actual_sunset, utc(date, sunset), description);
+        }
+    }
+
+    #[test]
+    fn test_calculate_twilight() {
+        let date = NaiveDate::from_ymd_opt(2025, 3, 20).unwrap();
+        let utc = |hour: u32, minute: u32| date.and_hms_opt(hour, minute, 0).unwrap().and_utc();
+
+        // Berlin, spring equinox. Civil 05:35 / 18:54 CET, nautical 04:54 / 19:34 CET,
+        // astronomical 04:12 / 20:17 CET
+        let location = LocationConfig { latitude: 52.52, longitude: 13.40 };
+        let SunTimes::Regular { twilight, .. } = calculate_sun_times(&location, date).unwrap() else {
+            panic!("Berlin should have a sunrise and a sunset");
+        };
+
+        assert_close(twilight.civil_dawn.unwrap(), utc(4, 35), "Civil dawn");
+        assert_close(twilight.civil_dusk.unwrap(), utc(17, 54), "Civil dusk");
+        assert_close(twilight.nautical_dawn.unwrap(), utc(3, 54), "Nautical dawn");
+        assert_close(twilight.nautical_dusk.unwrap(), utc(18, 34), "Nautical dusk");
+        assert_close(twilight.astronomical_dawn.unwrap(), utc(3, 12), "Astronomical dawn");
+        assert_close(twilight.astronomical_dusk.unwrap(), utc(19, 17), "Astronomical dusk");
+        assert!(twilight.golden_hour_end.unwrap() < twilight.golden_hour.unwrap());
+
+        // Berlin, summer solstice: it never gets darker than nautical twilight
+        let date = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let SunTimes::Regular { twilight, .. } = calculate_sun_times(&location, date).unwrap() else {
+            panic!("Berlin should have a sunrise and a sunset");
+        };
+
+        assert!(twilight.civil_dusk.is_some());
+        assert!(twilight.astronomical_dawn.is_none());
+        assert!(twilight.astronomical_dusk.is_none());
+    }
+
+    #[test]
+    fn test_solar_elevation() {
+        let location = LocationConfig { latitude: 52.52, longitude: 13.40 };
+        let date = NaiveDate::from_ymd_opt(2025, 3, 20).unwrap();
+        let SunTimes::Regular { sunrise, twilight, .. } = calculate_sun_times(&location, date).unwrap() else {
+            panic!("Berlin should have a sunrise and a sunset");
+        };
+
+        // Sunrise includes refraction and the sun's radius, hence -0.833°
+        assert!((solar_elevation(&location, sunrise) + 0.833).abs() < 0.1);
+        assert!((solar_elevation(&location, twilight.civil_dawn.unwrap()) + 6.0).abs() < 0.1);
+        // Equinox: the sun culminates at 90° minus the latitude
+        let noon = date.and_hms_opt(11, 14, 0).unwrap().and_utc();
+        assert!((solar_elevation(&location, noon) - 37.48).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_calculate_sun_times_polar() {
+        // Longyearbyen and Tromsø
+        let test_cases = vec![
+            (78.22, 15.65, NaiveDate::from_ymd_opt(2025, 6, 21).unwrap(), SunTimes::PolarDay),
+            (78.22, 15.65, NaiveDate::from_ymd_opt(2025, 12, 21).unwrap(), SunTimes::PolarNight),
+            (-78.22, 15.65, NaiveDate::from_ymd_opt(2025, 6, 21).unwrap(), SunTimes::PolarNight),
+            (69.65, 18.96, NaiveDate::from_ymd_opt(2025, 7, 1).unwrap(), SunTimes::PolarDay),
+            (69.65, 18.96, NaiveDate::from_ymd_opt(2025, 12, 15).unwrap(), SunTimes::PolarNight),
+        ];
+
+        for (latitude, longitude, date, expected) in test_cases {
+            let location = LocationConfig { latitude, longitude };
+            assert_eq!(calculate_sun_times(&location, date).unwrap(), expected, "{}, {} on {}", latitude, longitude, date);
+        }
+    }
+}
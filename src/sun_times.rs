@@ -1,11 +1,100 @@
-use chrono::NaiveTime;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use crate::config::Config;
+use crate::config::LocationConfig;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-pub struct SunTimes {
-    pub sunrise: NaiveTime,
-    pub sunset: NaiveTime,
+pub enum SunTimes {
+    Regular {
+        sunrise: DateTime<Utc>,
+        sunset: DateTime<Utc>,
+        #[serde(default)]
+        twilight: Twilight,
+    },
+    // Midnight sun: the sun never sets
+    PolarDay,
+    // The sun never rises
+    PolarNight,
+}
+
+// Not every provider knows every event, and close to the poles some of them
+// (astronomical dusk in summer, for instance) don't happen at all
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct Twilight {
+    pub astronomical_dawn: Option<DateTime<Utc>>,
+    pub nautical_dawn: Option<DateTime<Utc>>,
+    pub civil_dawn: Option<DateTime<Utc>>,
+    // End of the morning golden hour
+    pub golden_hour_end: Option<DateTime<Utc>>,
+    // Start of the evening golden hour
+    pub golden_hour: Option<DateTime<Utc>>,
+    pub civil_dusk: Option<DateTime<Utc>>,
+    pub nautical_dusk: Option<DateTime<Utc>>,
+    pub astronomical_dusk: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SolarEvent {
+    #[serde(alias = "first_light")]
+    AstronomicalDawn,
+    NauticalDawn,
+    #[serde(alias = "dawn")]
+    CivilDawn,
+    Sunrise,
+    GoldenHourEnd,
+    GoldenHour,
+    Sunset,
+    #[serde(alias = "dusk")]
+    CivilDusk,
+    NauticalDusk,
+    #[serde(alias = "last_light")]
+    AstronomicalDusk,
+}
+
+impl SunTimes {
+    // None for polar days and nights, or when the event is unknown
+    pub fn event(&self, event: SolarEvent) -> Option<DateTime<Utc>> {
+        let SunTimes::Regular { sunrise, sunset, twilight } = self else {
+            return None;
+        };
+
+        match event {
+            SolarEvent::AstronomicalDawn => twilight.astronomical_dawn,
+            SolarEvent::NauticalDawn => twilight.nautical_dawn,
+            SolarEvent::CivilDawn => twilight.civil_dawn,
+            SolarEvent::Sunrise => Some(*sunrise),
+            SolarEvent::GoldenHourEnd => twilight.golden_hour_end,
+            SolarEvent::GoldenHour => twilight.golden_hour,
+            SolarEvent::Sunset => Some(*sunset),
+            SolarEvent::CivilDusk => twilight.civil_dusk,
+            SolarEvent::NauticalDusk => twilight.nautical_dusk,
+            SolarEvent::AstronomicalDusk => twilight.astronomical_dusk,
+        }
+    }
+
+    // The same times on another day, close enough when they're only a few
+    // days apart. Polar days and nights stay as they are
+    pub fn shifted(&self, by: Duration) -> SunTimes {
+        let SunTimes::Regular { sunrise, sunset, twilight } = self else {
+            return self.clone();
+        };
+        let shift = |time: Option<DateTime<Utc>>| time.map(|time| time + by);
+
+        SunTimes::Regular {
+            sunrise: *sunrise + by,
+            sunset: *sunset + by,
+            twilight: Twilight {
+                astronomical_dawn: shift(twilight.astronomical_dawn),
+                nautical_dawn: shift(twilight.nautical_dawn),
+                civil_dawn: shift(twilight.civil_dawn),
+                golden_hour_end: shift(twilight.golden_hour_end),
+                golden_hour: shift(twilight.golden_hour),
+                civil_dusk: shift(twilight.civil_dusk),
+                nautical_dusk: shift(twilight.nautical_dusk),
+                astronomical_dusk: shift(twilight.astronomical_dusk),
+            },
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -13,17 +102,51 @@ struct ApiResponse {
     results: ApiResults,
 }
 
+// Both APIs send null or placeholder values around the poles, in which case
+// the provider fails and the next one (usually "calculated") takes over.
+// Twilight events are best effort: missing ones are simply left out
 #[derive(Debug, Deserialize)]
 struct ApiResults {
-    sunrise: String,
-    sunset: String,
+    sunrise: Option<String>,
+    sunset: Option<String>,
+    // sunrisesunset.io
+    first_light: Option<String>,
+    dawn: Option<String>,
+    golden_hour: Option<String>,
+    dusk: Option<String>,
+    last_light: Option<String>,
+    // sunrise-sunset.org
+    astronomical_twilight_begin: Option<String>,
+    nautical_twilight_begin: Option<String>,
+    civil_twilight_begin: Option<String>,
+    civil_twilight_end: Option<String>,
+    nautical_twilight_end: Option<String>,
+    astronomical_twilight_end: Option<String>,
+}
+
+// sunrise-sunset.org answers with 1970-01-01T00:00:01 when there is no event
+const PLACEHOLDER_TIMESTAMP_LIMIT: i64 = 24 * 60 * 60;
+
+pub const SUNRISESUNSET_IO_URL: &str = "https://api.sunrisesunset.io";
+pub const SUNRISE_SUNSET_ORG_URL: &str = "https://api.sunrise-sunset.org";
+
+pub fn build_sunrisesunset_url(base_url: &str, location: &LocationConfig, date: NaiveDate) -> String {
+    format!(
+        "{}/json?lat={}&lng={}&date={}&time_format=unix",
+        base_url,
+        location.latitude,
+        location.longitude,
+        date.format("%Y-%m-%d"),
+    )
 }
 
-pub fn build_sunrisesunset_url(config: &Config) -> String {
+pub fn build_sunrise_sunset_org_url(base_url: &str, location: &LocationConfig, date: NaiveDate) -> String {
     format!(
-        "https://api.sunrisesunset.io/json?lat={}&lng={}&time_format=unix",
-        config.location.latitude,
-        config.location.longitude,
+        "{}/json?lat={}&lng={}&date={}&formatted=0",
+        base_url,
+        location.latitude,
+        location.longitude,
+        date.format("%Y-%m-%d"),
     )
 }
 
@@ -31,13 +154,61 @@ pub fn fetch_sunrise_sunset(url: &str) -> Result<SunTimes, Box<dyn std::error::E
     let response = reqwest::blocking::get(url)?;
     let api_response: ApiResponse = response.json()?;
 
-    let sunrise_timestamp: i64 = api_response.results.sunrise.parse()?;
-    let sunset_timestamp: i64 = api_response.results.sunset.parse()?;
+    let parse = |value: Option<String>, event: &str| -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+        let timestamp: i64 = value.ok_or(format!("No {} in response", event))?.parse()?;
+        let datetime = DateTime::from_timestamp(timestamp, 0).ok_or(format!("Invalid {} timestamp", event))?;
+
+        Ok(valid_event(datetime, event)?)
+    };
+
+    let results = api_response.results;
+    let sunrise = parse(results.sunrise, "sunrise")?;
+    let sunset = parse(results.sunset, "sunset")?;
+    let twilight = Twilight {
+        astronomical_dawn: parse(results.first_light, "first_light").ok(),
+        civil_dawn: parse(results.dawn, "dawn").ok(),
+        golden_hour: parse(results.golden_hour, "golden_hour").ok(),
+        civil_dusk: parse(results.dusk, "dusk").ok(),
+        astronomical_dusk: parse(results.last_light, "last_light").ok(),
+        ..Twilight::default()
+    };
+
+    Ok(SunTimes::Regular { sunrise, sunset, twilight })
+}
+
+// sunrise-sunset.org returns ISO 8601 datetimes when called with formatted=0
+pub fn fetch_sunrise_sunset_org(url: &str) -> Result<SunTimes, Box<dyn std::error::Error>> {
+    let response = reqwest::blocking::get(url)?;
+    let api_response: ApiResponse = response.json()?;
+
+    let parse = |value: Option<String>, event: &str| -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+        let datetime = DateTime::parse_from_rfc3339(&value.ok_or(format!("No {} in response", event))?)?;
+
+        Ok(valid_event(datetime.to_utc(), event)?)
+    };
+
+    let results = api_response.results;
+    let sunrise = parse(results.sunrise, "sunrise")?;
+    let sunset = parse(results.sunset, "sunset")?;
+    let twilight = Twilight {
+        astronomical_dawn: parse(results.astronomical_twilight_begin, "astronomical_twilight_begin").ok(),
+        nautical_dawn: parse(results.nautical_twilight_begin, "nautical_twilight_begin").ok(),
+        civil_dawn: parse(results.civil_twilight_begin, "civil_twilight_begin").ok(),
+        civil_dusk: parse(results.civil_twilight_end, "civil_twilight_end").ok(),
+        nautical_dusk: parse(results.nautical_twilight_end, "nautical_twilight_end").ok(),
+        astronomical_dusk: parse(results.astronomical_twilight_end, "astronomical_twilight_end").ok(),
+        ..Twilight::default()
+    };
+
+    Ok(SunTimes::Regular { sunrise, sunset, twilight })
+}
 
-    let sunrise = chrono::DateTime::from_timestamp(sunrise_timestamp, 0).ok_or("Invalid sunrise timestamp")?.time();
-    let sunset = chrono::DateTime::from_timestamp(sunset_timestamp, 0).ok_or("Invalid sunset timestamp")?.time();
+fn valid_event(datetime: DateTime<Utc>, event: &str) -> Result<DateTime<Utc>, String> {
+    if datetime.timestamp() < PLACEHOLDER_TIMESTAMP_LIMIT {
+        return Err(format!("No {} on this day", event));
+    }
 
-    Ok(SunTimes { sunrise, sunset })
+    Ok(datetime)
 }
 
 #[cfg(test)]
@@ -73,12 +244,137 @@ mod tests {
         let mock_url = format!("{}{}", server.url(), url_path);
 
         let result = fetch_sunrise_sunset(&mock_url).unwrap();
-        let expected_result = SunTimes {
-            sunrise: chrono::DateTime::from_timestamp(1755402810, 0).unwrap().time(),
-            sunset: chrono::DateTime::from_timestamp(1755455425, 0).unwrap().time(),
+        let expected_result = SunTimes::Regular {
+            sunrise: DateTime::from_timestamp(1755402810, 0).unwrap(),
+            sunset: DateTime::from_timestamp(1755455425, 0).unwrap(),
+            twilight: Twilight {
+                astronomical_dawn: DateTime::from_timestamp(1755393963, 0),
+                civil_dawn: DateTime::from_timestamp(1755400509, 0),
+                golden_hour: DateTime::from_timestamp(1755452571, 0),
+                civil_dusk: DateTime::from_timestamp(1755457726, 0),
+                astronomical_dusk: DateTime::from_timestamp(1755464272, 0),
+                ..Twilight::default()
+            },
         };
 
         assert_eq!(result, expected_result);
         mock.assert();
     }
+
+    fn utc(datetime: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(datetime).unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_fetch_sunrise_sunset_org() {
+        let mut server = Server::new();
+        let url_path = format!("/json?lat={}&lng={}&date={}&formatted=0", "52.56", "13.39", "2025-08-17");
+        let mock = server.mock("GET", url_path.as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+              "results": {
+                "sunrise": "2025-08-17T03:58:12+00:00",
+                "sunset": "2025-08-17T18:25:40+00:00",
+                "solar_noon": "2025-08-17T11:11:56+00:00",
+                "day_length": 52048,
+                "civil_twilight_begin": "2025-08-17T03:19:48+00:00",
+                "civil_twilight_end": "2025-08-17T19:04:04+00:00",
+                "nautical_twilight_begin": "2025-08-17T02:29:43+00:00",
+                "nautical_twilight_end": "2025-08-17T19:54:09+00:00",
+                "astronomical_twilight_begin": "2025-08-17T01:25:06+00:00",
+                "astronomical_twilight_end": "2025-08-17T20:58:46+00:00"
+              },
+              "status": "OK",
+              "tzid": "UTC"
+            }"#)
+            .create();
+        let mock_url = format!("{}{}", server.url(), url_path);
+
+        let result = fetch_sunrise_sunset_org(&mock_url).unwrap();
+        let expected_result = SunTimes::Regular {
+            sunrise: utc("2025-08-17T03:58:12Z"),
+            sunset: utc("2025-08-17T18:25:40Z"),
+            twilight: Twilight {
+                astronomical_dawn: Some(utc("2025-08-17T01:25:06Z")),
+                nautical_dawn: Some(utc("2025-08-17T02:29:43Z")),
+                civil_dawn: Some(utc("2025-08-17T03:19:48Z")),
+                civil_dusk: Some(utc("2025-08-17T19:04:04Z")),
+                nautical_dusk: Some(utc("2025-08-17T19:54:09Z")),
+                astronomical_dusk: Some(utc("2025-08-17T20:58:46Z")),
+                ..Twilight::default()
+            },
+        };
+
+        assert_eq!(result, expected_result);
+        mock.assert();
+    }
+
+    #[test]
+    fn test_fetch_polar_responses_fail() {
+        let mut server = Server::new();
+        let io_mock = server.mock("GET", "/io")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"results": {"date": "2025-06-21", "sunrise": null, "sunset": null}, "status": "OK"}"#)
+            .create();
+        let org_mock = server.mock("GET", "/org")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+              "results": {
+                "sunrise": "1970-01-01T00:00:01+00:00",
+                "sunset": "1970-01-01T00:00:01+00:00",
+                "day_length": 0
+              },
+              "status": "OK"
+            }"#)
+            .create();
+
+        assert!(fetch_sunrise_sunset(&format!("{}/io", server.url())).is_err());
+        assert!(fetch_sunrise_sunset_org(&format!("{}/org", server.url())).is_err());
+        io_mock.assert();
+        org_mock.assert();
+    }
+
+    #[test]
+    fn test_event() {
+        let sun_times = SunTimes::Regular {
+            sunrise: utc("2025-08-17T03:58:12Z"),
+            sunset: utc("2025-08-17T18:25:40Z"),
+            twilight: Twilight {
+                civil_dusk: Some(utc("2025-08-17T19:04:04Z")),
+                ..Twilight::default()
+            },
+        };
+
+        assert_eq!(sun_times.event(SolarEvent::Sunrise), Some(utc("2025-08-17T03:58:12Z")));
+        assert_eq!(sun_times.event(SolarEvent::Sunset), Some(utc("2025-08-17T18:25:40Z")));
+        assert_eq!(sun_times.event(SolarEvent::CivilDusk), Some(utc("2025-08-17T19:04:04Z")));
+        assert_eq!(sun_times.event(SolarEvent::NauticalDusk), None);
+        assert_eq!(SunTimes::PolarDay.event(SolarEvent::Sunrise), None);
+    }
+
+    #[test]
+    fn test_shifted() {
+        let sun_times = SunTimes::Regular {
+            sunrise: utc("2025-08-17T03:58:12Z"),
+            sunset: utc("2025-08-17T18:25:40Z"),
+            twilight: Twilight {
+                civil_dusk: Some(utc("2025-08-17T19:04:04Z")),
+                ..Twilight::default()
+            },
+        };
+        let expected_sun_times = SunTimes::Regular {
+            sunrise: utc("2025-08-19T03:58:12Z"),
+            sunset: utc("2025-08-19T18:25:40Z"),
+            twilight: Twilight {
+                civil_dusk: Some(utc("2025-08-19T19:04:04Z")),
+                ..Twilight::default()
+            },
+        };
+
+        assert_eq!(sun_times.shifted(Duration::days(2)), expected_sun_times);
+        assert_eq!(SunTimes::PolarNight.shifted(Duration::days(2)), SunTimes::PolarNight);
+    }
 }